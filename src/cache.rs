@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// Output of a component stored for the `cache_ttl` option
+#[derive(Serialize, Deserialize)]
+pub struct CachedOutput {
+    /// Minimum width returned by the prepare phase, so that the other components
+    /// are laid out the same way as when the output was rendered
+    pub min_width: Option<usize>,
+    pub output: String,
+}
+
+/// Directory where the rendered output of components is cached
+fn cache_dir() -> io::Result<PathBuf> {
+    let cache_base = match env::var("XDG_CACHE_HOME") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => PathBuf::from(env::var("HOME").map_err(io::Error::other)?).join(".cache"),
+    };
+    Ok(cache_base.join("rust-motd"))
+}
+
+fn cache_file(name: &str) -> io::Result<PathBuf> {
+    Ok(cache_dir()?.join(format!("{name}.json")))
+}
+
+/// Cached output of the component `name` together with its age if it is younger than `ttl`
+pub fn read(name: &str, ttl: Duration) -> Option<(CachedOutput, Duration)> {
+    read_with_age(name).filter(|(_, age)| *age < ttl)
+}
+
+/// Cached output of the component `name` together with its age, however old it is
+pub fn read_with_age(name: &str) -> Option<(CachedOutput, Duration)> {
+    let path = cache_file(name).ok()?;
+    let age = SystemTime::now()
+        .duration_since(fs::metadata(&path).ok()?.modified().ok()?)
+        .ok()?;
+    let cached = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    Some((cached, age))
+}
+
+/// Store the output of the component `name`
+pub fn write(name: &str, cached: &CachedOutput) -> io::Result<()> {
+    fs::create_dir_all(cache_dir()?)?;
+    fs::write(cache_file(name)?, serde_json::to_string(cached)?)
+}
@@ -0,0 +1,249 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use termion::{color, style};
+use thiserror::Error;
+
+use crate::component::Severity;
+use crate::config::global_config::GlobalConfig;
+
+/// Escape sequence setting the foreground color
+/// Empty if colors are disabled, so that the output is plain text
+pub fn fg<C: color::Color>(c: C, enabled: bool) -> String {
+    match enabled {
+        true => color::Fg(c).to_string(),
+        false => String::new(),
+    }
+}
+
+/// Escape sequence making the text dim
+/// Empty if colors are disabled, so that the output is plain text
+pub fn dim(enabled: bool) -> String {
+    match enabled {
+        true => style::Faint.to_string(),
+        false => String::new(),
+    }
+}
+
+/// Escape sequence resetting the color and style
+/// Empty if colors are disabled, so that the output is plain text
+pub fn reset(enabled: bool) -> String {
+    match enabled {
+        true => style::Reset.to_string(),
+        false => String::new(),
+    }
+}
+
+/// Color of the filled part of a progress bar
+/// Interpolated from `progress_full_colors` if configured,
+/// otherwise green, yellow or red depending on the thresholds
+pub fn full_color(global_config: &GlobalConfig, ratio: f64) -> String {
+    if let Some(full_colors) = &global_config.progress_full_colors {
+        if !full_colors.is_empty() {
+            return fg(interpolate(full_colors, ratio), global_config.color);
+        }
+    }
+    threshold_color(
+        global_config,
+        ratio,
+        global_config.progress_warn_threshold,
+        global_config.progress_bad_threshold,
+    )
+}
+
+/// `ok_color`, `warn_color` or `bad_color` depending on the given thresholds
+pub fn threshold_color(global_config: &GlobalConfig, ratio: f64, warn: f64, bad: f64) -> String {
+    severity_color(global_config, Severity::of(ratio, warn, bad))
+}
+
+/// Color of values of the given severity, green, yellow and red unless configured otherwise
+pub fn severity_color(global_config: &GlobalConfig, severity: Severity) -> String {
+    let color = match severity {
+        Severity::Ok => global_config.ok_color,
+        Severity::Warn => global_config.warn_color,
+        Severity::Crit => global_config.bad_color,
+    };
+    fg(color, global_config.color)
+}
+
+/// Green, yellow and red of color gradients
+const GRADIENT: [HexColor; 3] = [
+    HexColor(color::Rgb(0x2e, 0xcc, 0x71)),
+    HexColor(color::Rgb(0xf1, 0xc4, 0x0f)),
+    HexColor(color::Rgb(0xe7, 0x4c, 0x3c)),
+];
+
+/// 24-bit color going from green through yellow (at 0.5) to red (at 1)
+pub fn gradient_color(global_config: &GlobalConfig, ratio: f64) -> String {
+    fg(interpolate(&GRADIENT, ratio), global_config.color)
+}
+
+/// Interpolate linearly between colors evenly spread over the range [0, 1]
+fn interpolate(colors: &[HexColor], ratio: f64) -> color::Rgb {
+    let position = ratio.clamp(0.0, 1.0) * (colors.len() - 1) as f64;
+    let index = (position.floor() as usize).min(colors.len() - 1);
+    let (from, to) = (colors[index].0, colors[(index + 1).min(colors.len() - 1)].0);
+    let t = position - index as f64;
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    color::Rgb(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+/// Remove escape sequences from the output of external commands and services
+pub fn strip_escapes(text: &str) -> Cow<'_, str> {
+    lazy_static! {
+        static ref ESCAPE_REGEX: Regex = Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap();
+    }
+    ESCAPE_REGEX.replace_all(text, "")
+}
+
+/// Terminal colors that can be used in the configuration file
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorName {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    LightBlack,
+    LightRed,
+    LightGreen,
+    LightYellow,
+    LightBlue,
+    LightMagenta,
+    LightCyan,
+    LightWhite,
+}
+
+#[derive(Error, Debug)]
+#[error(
+    "Invalid color {0:?}. Options are black, red, green, yellow, blue, magenta, cyan, white, \
+    and light variants of each (e.g. light_blue)"
+)]
+pub struct InvalidColorError(String);
+
+#[derive(Error, Debug)]
+#[error("Invalid color {0:?}, expected a hex color like \"#2ecc71\"")]
+pub struct InvalidHexColorError(String);
+
+impl ColorName {
+    fn as_color(self) -> &'static dyn color::Color {
+        match self {
+            ColorName::Black => &color::Black,
+            ColorName::Red => &color::Red,
+            ColorName::Yellow => &color::Yellow,
+            ColorName::Green => &color::Green,
+            ColorName::Blue => &color::Blue,
+            ColorName::Magenta => &color::Magenta,
+            ColorName::Cyan => &color::Cyan,
+            ColorName::White => &color::White,
+            ColorName::LightBlack => &color::LightBlack,
+            ColorName::LightRed => &color::LightRed,
+            ColorName::LightYellow => &color::LightYellow,
+            ColorName::LightGreen => &color::LightGreen,
+            ColorName::LightBlue => &color::LightBlue,
+            ColorName::LightMagenta => &color::LightMagenta,
+            ColorName::LightCyan => &color::LightCyan,
+            ColorName::LightWhite => &color::LightWhite,
+        }
+    }
+}
+
+impl color::Color for ColorName {
+    fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_color().write_fg(f)
+    }
+
+    fn write_bg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_color().write_bg(f)
+    }
+}
+
+impl FromStr for ColorName {
+    type Err = InvalidColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_plain::from_str(s).map_err(|_| InvalidColorError(s.to_string()))
+    }
+}
+
+/// 24-bit color given as a hex string like "#2ecc71" in the configuration file
+#[derive(Debug, Clone, Copy)]
+pub struct HexColor(pub color::Rgb);
+
+impl FromStr for HexColor {
+    type Err = InvalidHexColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidHexColorError(s.to_string());
+        let hex = s.strip_prefix('#').ok_or_else(invalid)?;
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        Ok(HexColor(color::Rgb(
+            component(0)?,
+            component(2)?,
+            component(4)?,
+        )))
+    }
+}
+
+impl<'de> Deserialize<'de> for HexColor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Color given either by name or as a hex string in the configuration file
+#[derive(Debug, Clone, Copy)]
+pub enum ConfigColor {
+    Named(ColorName),
+    Hex(HexColor),
+}
+
+impl color::Color for ConfigColor {
+    fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigColor::Named(name) => name.write_fg(f),
+            ConfigColor::Hex(hex) => hex.0.write_fg(f),
+        }
+    }
+
+    fn write_bg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigColor::Named(name) => name.write_bg(f),
+            ConfigColor::Hex(hex) => hex.0.write_bg(f),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigColor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        match s.starts_with('#') {
+            true => s
+                .parse()
+                .map(ConfigColor::Hex)
+                .map_err(serde::de::Error::custom),
+            false => s
+                .parse()
+                .map(ConfigColor::Named)
+                .map_err(serde::de::Error::custom),
+        }
+    }
+}
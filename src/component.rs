@@ -1,5 +1,14 @@
 use crate::config::global_config::GlobalConfig;
 use async_trait::async_trait;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fmt::Display;
+use std::io;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread::available_parallelism;
+use std::time::Duration;
 
 /// Boxed component with all other traits
 // Send is required because print is async
@@ -17,6 +26,159 @@ pub struct Constraints {
     pub min_width: Option<usize>,
 }
 
+/// Severity of the state shown by a component, see `severity_exit_code` in the global configuration
+/// The discriminants are the exit codes used by monitoring plugins (e.g. Nagios)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    #[default]
+    Ok = 0,
+    Warn = 1,
+    Crit = 2,
+}
+
+impl Severity {
+    /// Severity of `value` with respect to the given thresholds, matching `colors::threshold_color`
+    pub fn of(value: f64, warn: f64, bad: f64) -> Self {
+        if value >= bad {
+            Severity::Crit
+        } else if value >= warn {
+            Severity::Warn
+        } else {
+            Severity::Ok
+        }
+    }
+}
+
+/// Set when any component fails, see `error_exit_code` in the global configuration
+static COMPONENT_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Name of each failed component with the reason of its first failure
+static FAILURES: Mutex<Vec<(&str, String)>> = Mutex::new(vec![]);
+
+thread_local! {
+    /// Set when a component fails on this thread
+    /// Each component is prepared and printed on its own thread
+    static FAILED_ON_THIS_THREAD: Cell<bool> = const { Cell::new(false) };
+
+    /// Name of the component running on this thread, used to attribute failures
+    static CURRENT_COMPONENT: Cell<&'static str> = const { Cell::new("unknown") };
+}
+
+/// Set the name of the component running on the current thread
+pub fn set_current_component(name: &'static str) {
+    CURRENT_COMPONENT.with(|current| current.set(name));
+}
+
+/// Record that the component running on this thread failed
+/// Components should normally use `print_error` instead
+pub fn report_failure(reason: impl Display) {
+    report_failure_of(CURRENT_COMPONENT.with(|current| current.get()), reason);
+    FAILED_ON_THIS_THREAD.with(|failed| failed.set(true));
+}
+
+/// Record that the component `name` failed
+pub fn report_failure_of(name: &'static str, reason: impl Display) {
+    COMPONENT_FAILED.store(true, Ordering::Relaxed);
+    let mut failures = FAILURES.lock().unwrap();
+    if !failures.iter().any(|(failed, _)| *failed == name) {
+        failures.push((name, reason.to_string()));
+    }
+}
+
+/// Failed components with the reason of their first failure, in the order they failed
+pub fn failures() -> Vec<(&'static str, String)> {
+    FAILURES.lock().unwrap().clone()
+}
+
+/// Whether any component has failed so far
+pub fn any_failed() -> bool {
+    COMPONENT_FAILED.load(Ordering::Relaxed)
+}
+
+/// Whether a failure was reported on the current thread
+pub fn failed_on_this_thread() -> bool {
+    FAILED_ON_THIS_THREAD.with(|failed| failed.get())
+}
+
+/// Print an error of a component in place of its output and record the failure
+pub fn print_error(label: &str, err: impl Display) {
+    println!("{label}: {err}");
+    report_failure(err);
+}
+
+/// Number of CPUs available to rust-motd
+/// If it cannot be determined (e.g. in restricted sandboxes), one CPU is assumed
+/// and a warning is printed to stderr once per run
+pub fn available_cpus() -> NonZeroUsize {
+    cpus_or_one(available_parallelism())
+}
+
+/// The number of CPUs given by `parallelism`, or one if it could not be determined
+fn cpus_or_one(parallelism: io::Result<NonZeroUsize>) -> NonZeroUsize {
+    static WARNED: Once = Once::new();
+    parallelism.unwrap_or_else(|err| {
+        WARNED.call_once(|| {
+            eprintln!("Warning: cannot determine the number of CPUs ({err}), assuming 1")
+        });
+        NonZeroUsize::MIN
+    })
+}
+
+/// `Ok` if no problems were found by `Component::validate` or a similar check
+pub fn validation_result(problems: Vec<String>) -> Result<(), Vec<String>> {
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(problems),
+    }
+}
+
+/// Progress bars are never made narrower than this, even if the line overflows the width
+pub const MIN_BAR_WIDTH: usize = 7;
+/// Names are never truncated to fewer characters than this
+pub const MIN_NAME_WIDTH: usize = 5;
+
+/// Layout of a line made of a name column, a progress bar and other columns of fixed width
+/// This makes sure that nothing underflows when the available width is too small
+/// In that case, the name column is shrunk first and then the progress bar
+pub struct BarLayout {
+    pub name_width: usize,
+    pub bar_width: usize,
+}
+
+impl BarLayout {
+    /// Fit the name column of `name_width` into `width` together with a bar and
+    /// `fixed_width` characters of other columns (including separating spaces)
+    pub fn new(width: usize, name_width: usize, fixed_width: usize) -> Self {
+        let available = width.saturating_sub(fixed_width);
+        let name_width =
+            name_width.min(available.saturating_sub(MIN_BAR_WIDTH).max(MIN_NAME_WIDTH));
+        BarLayout {
+            name_width,
+            bar_width: available.saturating_sub(name_width).max(MIN_BAR_WIDTH),
+        }
+    }
+
+    /// Truncate `name` with an ellipsis so that it fits the name column
+    pub fn name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        truncate_name(name, self.name_width)
+    }
+}
+
+/// Truncate `name` to at most `width` characters, ending with an ellipsis if
+/// there is room for it
+pub fn truncate_name(name: &str, width: usize) -> Cow<'_, str> {
+    const ELLIPSIS: &str = "...";
+    if name.chars().count() <= width {
+        return Cow::Borrowed(name);
+    }
+    if width < ELLIPSIS.len() {
+        return Cow::Owned(name.chars().take(width).collect());
+    }
+    let mut truncated: String = name.chars().take(width - ELLIPSIS.len()).collect();
+    truncated += ELLIPSIS;
+    Cow::Owned(truncated)
+}
+
 /// This trait should be implemented for all components
 /// (component being all the things the motd can print like banner, memory, etc.).
 #[async_trait]
@@ -32,6 +194,51 @@ pub trait Component {
 
     /// Print the component to stdout
     async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>);
+
+    /// Return the data of the (prepared) component for the JSON output format
+    /// Components that do not support it return `Null` and are omitted from the output
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+
+    /// Return the (prepared) component rendered as Markdown for the Markdown output format
+    /// Components that do not support it return `None` and their plain text output is used
+    fn to_markdown(&self) -> Option<String> {
+        None
+    }
+
+    /// Return the severity of the state shown by the (prepared) component
+    /// Components without thresholds are always `Ok`
+    fn severity(&self) -> Severity {
+        Severity::Ok
+    }
+
+    /// Whether the (prepared) component prints nothing, in which case
+    /// no separator is printed for it
+    fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Render the (prepared) component as a short text without a line break, to be
+    /// printed on one line with other components configured as `inline`
+    /// Components returning `None` are printed as blocks even if `inline` is set
+    fn render_inline(&self, _global_config: &GlobalConfig) -> Option<String> {
+        None
+    }
+
+    /// Check the configuration of the component before anything is prepared or printed
+    /// All problems found are returned, so that they can be reported at once
+    fn validate(&self, _global_config: &GlobalConfig) -> Result<(), Vec<String>> {
+        Ok(())
+    }
+
+    /// Return how old the data shown by the (prepared) component is, e.g. when it is
+    /// computed from the state of a previous run
+    /// Components showing current data return `None`
+    /// Data older than `stale_after` is marked as such after the output of the component
+    fn data_age(&self) -> Option<Duration> {
+        None
+    }
 }
 
 /// Implement the default prepare method which returns the component unmodified and no constraints
@@ -61,3 +268,51 @@ macro_rules! default_prepare {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_name_counts_characters() {
+        assert_eq!(truncate_name("nginx", 5), "nginx");
+        assert_eq!(truncate_name("docker-dcd9a8c7", 10), "docker-...");
+        // Multi-byte characters are never cut in the middle
+        assert_eq!(truncate_name("žluťoučký-kůň", 8), "žluťo...");
+    }
+
+    #[test]
+    fn cpus_fall_back_to_one() {
+        let sandboxed = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(cpus_or_one(Err(sandboxed)).get(), 1);
+        assert_eq!(cpus_or_one(Ok(NonZeroUsize::new(4).unwrap())).get(), 4);
+    }
+
+    #[test]
+    fn bar_layout_at_width_2() {
+        // Both columns keep their minimum width rather than underflowing
+        let layout = BarLayout::new(2, 20, 0);
+        assert_eq!(layout.name_width, MIN_NAME_WIDTH);
+        assert_eq!(layout.bar_width, MIN_BAR_WIDTH);
+        assert_eq!(
+            layout.name("docker-dcd9a8c7").chars().count(),
+            MIN_NAME_WIDTH
+        );
+
+        let layout = BarLayout::new(2, 20, 10);
+        assert_eq!(layout.name_width, MIN_NAME_WIDTH);
+        assert_eq!(layout.bar_width, MIN_BAR_WIDTH);
+
+        // A name shorter than the minimum is not padded
+        let layout = BarLayout::new(2, 2, 0);
+        assert_eq!(layout.name_width, 2);
+        assert_eq!(layout.bar_width, MIN_BAR_WIDTH);
+        assert_eq!(layout.name("ab"), "ab");
+    }
+
+    #[test]
+    fn truncate_name_without_room_for_ellipsis() {
+        assert_eq!(truncate_name("docker", 2), "do");
+        assert_eq!(truncate_name("docker", 0), "");
+    }
+}
@@ -1,48 +1,46 @@
 use async_trait::async_trait;
+use figlet_rs::FIGlet;
 use serde::Deserialize;
-use termion::{color, style};
 use thiserror::Error;
 
+use crate::colors::{self, ColorName};
 use crate::command::{BetterCommand, BetterCommandError};
-use crate::component::Component;
+use crate::component::{print_error, Component};
+use crate::components::host_info::hostname;
 use crate::config::global_config::GlobalConfig;
+use crate::config::ColorOption;
 use crate::default_prepare;
 
+/// Large text at the top of the MOTD, either generated by `command` or
+/// rendered from `text` as figlet text
 #[derive(Debug, Deserialize)]
 pub struct Banner {
-    color: BannerColor,
-    command: String,
+    // Taken from the common `color` option, see `with_color`
+    #[serde(skip)]
+    color: Option<ColorName>,
+    command: Option<String>,
+    /// Text rendered as figlet text (default: the hostname)
+    text: Option<String>,
+    /// Figlet font, one of the bundled fonts (e.g. slant) or the path of a font file
+    font: Option<String>,
 }
 
 #[async_trait]
 impl Component for Banner {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error()
-            .unwrap_or_else(|err| println!("Banner error: {}", err));
-        println!();
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>) {
+        self.print_or_error(global_config, width)
+            .unwrap_or_else(|err| print_error("Banner error", err));
     }
     default_prepare!();
-}
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "snake_case")]
-enum BannerColor {
-    Black,
-    Red,
-    Green,
-    Yellow,
-    Blue,
-    Magenta,
-    Cyan,
-    White,
-    LightBlack,
-    LightRed,
-    LightGreen,
-    LightYellow,
-    LightBlue,
-    LightMagenta,
-    LightCyan,
-    LightWhite,
+    fn validate(&self, _global_config: &GlobalConfig) -> Result<(), Vec<String>> {
+        match (&self.command, &self.text, &self.font) {
+            (Some(_), Some(_), _) | (Some(_), _, Some(_)) => Err(vec![
+                "command cannot be combined with text or font".to_string(),
+            ]),
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -52,37 +50,93 @@ pub enum BannerError {
 
     #[error(transparent)]
     IOError(#[from] std::io::Error),
+
+    #[error("Cannot load font {font:?}: {message}")]
+    Font { font: String, message: String },
 }
 
 impl Banner {
-    pub fn print_or_error(self) -> Result<(), BannerError> {
-        // We probably don't have to handle command not found for sh
-        let output = BetterCommand::new("sh")
-            .arg("-c")
-            .arg(&self.command)
-            .check_status_and_get_output_string()?;
+    /// Color of the text given by the common `color` option, if it is a color name
+    pub fn with_color(mut self, color: Option<ColorOption>) -> Self {
+        if let Some(ColorOption::Named(color)) = color {
+            self.color = Some(color);
+        }
+        self
+    }
+
+    pub fn print_or_error(
+        self,
+        global_config: &GlobalConfig,
+        width: Option<usize>,
+    ) -> Result<(), BannerError> {
+        let output = match &self.command {
+            // We probably don't have to handle command not found for sh
+            Some(command) => BetterCommand::new("sh")
+                .arg("-c")
+                .arg(command)
+                .check_status_and_get_output_string()?,
+            None => {
+                let text = match self.text {
+                    Some(text) => text,
+                    None => hostname()?,
+                };
+                // The terminal if there is one, otherwise the other components
+                let max_width = termion::terminal_size()
+                    .map(|(columns, _)| columns as usize)
+                    .unwrap_or(width.unwrap_or(global_config.progress_width));
+                render_figlet(&text, &load_font(self.font.as_deref())?, max_width)
+            }
+        };
 
-        let banner_color = match self.color {
-            BannerColor::Black => color::Black.fg_str(),
-            BannerColor::Red => color::Red.fg_str(),
-            BannerColor::Yellow => color::Yellow.fg_str(),
-            BannerColor::Green => color::Green.fg_str(),
-            BannerColor::Blue => color::Blue.fg_str(),
-            BannerColor::Magenta => color::Magenta.fg_str(),
-            BannerColor::Cyan => color::Cyan.fg_str(),
-            BannerColor::White => color::White.fg_str(),
-            BannerColor::LightBlack => color::LightBlack.fg_str(),
-            BannerColor::LightRed => color::LightRed.fg_str(),
-            BannerColor::LightYellow => color::LightYellow.fg_str(),
-            BannerColor::LightGreen => color::LightGreen.fg_str(),
-            BannerColor::LightBlue => color::LightBlue.fg_str(),
-            BannerColor::LightMagenta => color::LightMagenta.fg_str(),
-            BannerColor::LightCyan => color::LightCyan.fg_str(),
-            BannerColor::LightWhite => color::LightWhite.fg_str(),
+        let output = match global_config.color {
+            true => output.into(),
+            false => colors::strip_escapes(&output),
         };
 
-        println!("{}{}{}", banner_color, &output.trim_end(), style::Reset);
+        match self.color {
+            Some(color) => println!(
+                "{}{}{}",
+                colors::fg(color, global_config.color),
+                &output.trim_end(),
+                colors::reset(global_config.color)
+            ),
+            None => println!("{}", output.trim_end()),
+        }
 
         Ok(())
     }
 }
+
+/// Font given by the `font` option, the standard figlet font by default
+fn load_font(font: Option<&str>) -> Result<FIGlet, BannerError> {
+    let font = font.unwrap_or("standard");
+    match font {
+        "standard" => FIGlet::standard(),
+        "small" => FIGlet::small(),
+        "big" => FIGlet::big(),
+        "slant" => FIGlet::slant(),
+        path => FIGlet::from_file(path),
+    }
+    .map_err(|message| BannerError::Font {
+        font: font.into(),
+        message,
+    })
+}
+
+/// Render `text` as ASCII art in `font`
+/// Falls back to the plain text if the font cannot render it or the art is wider than `max_width`
+fn render_figlet(text: &str, font: &FIGlet, max_width: usize) -> String {
+    let art = match font.convert(text) {
+        Some(art) => art.to_string(),
+        None => return text.to_string(),
+    };
+    let art_width = art
+        .lines()
+        .map(|line| line.trim_end().chars().count())
+        .max()
+        .unwrap_or(0);
+    match art_width <= max_width {
+        true => art,
+        false => text.to_string(),
+    }
+}
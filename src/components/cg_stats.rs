@@ -1,77 +1,541 @@
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::error::Error;
-use std::fs::{self, File};
-use std::io::{self, BufRead, BufReader};
+use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::thread::available_parallelism;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
+use bytesize::ByteSize;
 use itertools::Itertools;
-use regex::Regex;
+use lazy_static::lazy_static;
+use regex::{Regex, RegexSet};
 use serde::{Deserialize, Serialize};
-use termion::{color, style};
-use walkdir::WalkDir;
+use serde_json::json;
+use systemstat::{Platform, System};
+use termion::color;
 
-use crate::component::{Component, Constraints, PrepareReturn};
+use crate::colors;
+use crate::component::{
+    available_cpus, report_failure, truncate_name, validation_result, BarLayout, Component,
+    Constraints, PrepareReturn, Severity, MIN_BAR_WIDTH,
+};
 use crate::config::global_config::GlobalConfig;
-use crate::constants::INDENT_WIDTH;
+use crate::config::{duration, ratio};
 use crate::default_prepare;
+use crate::progress_bar::format_bar_with_value;
+use crate::rounding::{round_duration, Granularity};
+use crate::state_lock::lock_state_file;
+
+const DEFAULT_MAX_NAME_LENGTH: usize = 23;
+const DEFAULT_CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const DEFAULT_SLICES: [&str; 2] = ["user.slice", "system.slice"];
+/// Name of the row showing the CPU usage of a whole slice
+const SLICE_TOTAL_NAME: &str = "(slice total)";
 
 /// A container for component configuration from the configuration file
 #[derive(Clone, Deserialize)]
 pub struct CgStats {
     /// File where to store Cgroup statistic needed by the next run
     state_file: String,
-    /// List only Cgroups with higher CPU usage (0.01 ~ 1%)
+    /// List only Cgroups with higher CPU usage (0.01 or "1%")
+    #[serde(deserialize_with = "ratio::deserialize")]
     threshold: f64,
+    /// Truncate service names longer than this (default 23)
+    max_name_length: Option<usize>,
+    /// Show only this many Cgroups with the highest CPU usage in each section
+    max_entries: Option<usize>,
+    /// Order in which the Cgroups are listed
+    #[serde(default)]
+    sort_by: SortOrder,
+    /// Show also the current memory usage of the Cgroups
+    #[serde(default)]
+    show_memory: bool,
+    /// Force Cgroup version (1 or 2) instead of detecting it
+    cgroup_version: Option<u8>,
+    /// Where the Cgroup hierarchy is mounted (default /sys/fs/cgroup)
+    cgroup_root: Option<PathBuf>,
+    /// Do not update the statistics if the previous state is younger than this
+    #[serde(default, deserialize_with = "duration::deserialize_optional")]
+    min_time_span: Option<Duration>,
+    /// Show a sparkline of the CPU usage during this many past runs
+    history_length: Option<usize>,
+    /// Slices whose children are listed (default user.slice and system.slice)
+    slices: Option<Vec<String>>,
+    /// Measure CPU usage over (approximately) this time span by keeping
+    /// timestamped snapshots of the state instead of a single state file
+    #[serde(default, deserialize_with = "duration::deserialize_optional")]
+    window: Option<Duration>,
+    /// Hide Cgroups whose (displayed) names match any of these regular expressions
+    exclude: Option<Vec<String>>,
+    /// List only Cgroups whose (displayed) names match any of these regular expressions
+    /// Cgroups matching `include` are listed even if they match `exclude`
+    include: Option<Vec<String>>,
+    /// Show whether the CPU usage went up or down since the previous run
+    #[serde(default)]
+    show_trend: bool,
+    /// Precision of the displayed time span
+    #[serde(default, alias = "duration_granularity")]
+    time_precision: TimePrecision,
+    /// Number of decimal places of the CPU usage percentage (default 0)
+    percent_decimals: Option<usize>,
+    /// Number of CPUs the CPU usage is relative to (default: the CPUs available
+    /// to rust-motd, which already accounts for cpuset and CPU quota restrictions)
+    cpu_count: Option<NonZeroUsize>,
+    /// Instead of truncating names longer than `max_name_length`, print
+    /// them in full on their own line with the bar on the next line
+    /// (only in the stacked layout)
+    #[serde(default)]
+    wrap_names: bool,
+    /// Whether to show the CPU usage as a percentage, as CPU time or both
+    #[serde(default)]
+    display_mode: DisplayMode,
+    /// Print nothing at all if no Cgroup exceeds the threshold
+    #[serde(default)]
+    hide_when_empty: bool,
+    /// Sum the Cgroups of each slice by the given category
+    #[serde(default)]
+    group_by: GroupBy,
+    /// Show nonzero CPU usage that would be rounded to 0% as "<1%"
+    #[serde(default)]
+    round_up_nonzero: bool,
+    /// Show how many Cgroups are listed out of all Cgroups in each section
+    #[serde(default)]
+    show_counts: bool,
+    /// Fixed width of the progress bars instead of filling the available width
+    bar_width: Option<usize>,
+    /// Show the CPU usage since boot instead of since the previous run,
+    /// which works without a previous state
+    #[serde(default)]
+    since_boot: bool,
+    /// How the sections of the slices are arranged
+    #[serde(default)]
+    layout: SliceLayout,
+    /// Also list Cgroups started since the previous run, marked as new,
+    /// and the Cgroups that stopped since then
+    #[serde(default)]
+    track_lifecycle: bool,
+    /// Also show the CPU usage of each slice as a whole, which includes
+    /// processes directly in the slice and Cgroups that are not listed
+    #[serde(default)]
+    include_slice_total: bool,
+    /// Show users other than the current one as user-1, user-2, etc.
+    #[serde(default)]
+    anonymize_users: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Default)]
+enum SortOrder {
+    #[default]
+    #[serde(alias = "name")] // Alias used to match lowercase spelling as well
+    Name,
+    #[serde(alias = "load")] // Alias used to match lowercase spelling as well
+    Load,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Default)]
+enum TimePrecision {
+    /// Seconds up to three minutes, whole minutes above
+    #[default]
+    #[serde(alias = "auto")] // Alias used to match lowercase spelling as well
+    Auto,
+    #[serde(alias = "seconds")] // Alias used to match lowercase spelling as well
+    Seconds,
+    #[serde(alias = "minutes")] // Alias used to match lowercase spelling as well
+    Minutes,
+    #[serde(alias = "hours")] // Alias used to match lowercase spelling as well
+    Hours,
+    #[serde(alias = "days")] // Alias used to match lowercase spelling as well
+    Days,
+}
+
+impl TimePrecision {
+    /// Granularity to which a time span of `secs` seconds is rounded
+    fn granularity(self, secs: u64) -> Granularity {
+        match self {
+            TimePrecision::Auto if secs < 180 => Granularity::Seconds,
+            TimePrecision::Auto | TimePrecision::Minutes => Granularity::Minutes,
+            TimePrecision::Seconds => Granularity::Seconds,
+            TimePrecision::Hours => Granularity::Hours,
+            TimePrecision::Days => Granularity::Days,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Default)]
+enum DisplayMode {
+    /// CPU usage relative to all CPUs
+    #[default]
+    #[serde(alias = "percent")] // Alias used to match lowercase spelling as well
+    Percent,
+    /// CPU time consumed during the time span
+    #[serde(alias = "cpu_seconds")] // Alias used to match lowercase spelling as well
+    CpuSeconds,
+    /// Both of the above
+    #[serde(alias = "both")] // Alias used to match lowercase spelling as well
+    Both,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Default)]
+enum GroupBy {
+    /// Each Cgroup is listed on its own
+    #[default]
+    #[serde(alias = "none")] // Alias used to match lowercase spelling as well
+    None,
+    /// Cgroups are summed per user owning them
+    #[serde(alias = "user")] // Alias used to match lowercase spelling as well
+    User,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Default)]
+enum SliceLayout {
+    /// Sections below each other
+    #[default]
+    #[serde(alias = "stacked")] // Alias used to match lowercase spelling as well
+    Stacked,
+    /// Sections next to each other if the width allows it
+    #[serde(alias = "side_by_side")] // Alias used to match lowercase spelling as well
+    SideBySide,
 }
 
 #[async_trait]
 impl Component for CgStats {
     fn prepare(self: Box<Self>, global_config: &GlobalConfig) -> PrepareReturn {
-        self.prepare_or_error(global_config)
+        let lock = match global_config.dry_run {
+            true => Ok(None),
+            false => lock_state_file(&self.state_file).inspect(|lock| {
+                if lock.is_none() {
+                    eprintln!(
+                        "cg_stats warning: {} is locked by another instance, not updating it",
+                        self.state_file
+                    );
+                }
+            }),
+        };
+        lock.map_err(Box::<dyn Error>::from)
+            .and_then(|lock| self.prepare_or_error(global_config, &RealFs, lock.is_some()))
             .map_err(|e| {
                 eprintln!("cg_stats error: {e}");
+                report_failure(&e);
                 e
             })
             .unwrap_or((self, Some(Constraints { min_width: None })))
     }
     async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
         println!("cg_stats component failed");
+        report_failure("component failed");
+    }
+
+    fn validate(&self, _global_config: &GlobalConfig) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+        if !(0.0..=1.0).contains(&self.threshold) {
+            problems.push(format!(
+                "threshold must be between 0 and 1 (or 0% and 100%), got {}",
+                self.threshold
+            ));
+        }
+        if let Some(version) = self.cgroup_version.filter(|v| ![1, 2].contains(v)) {
+            problems.push(format!("Unsupported cgroup_version {version}"));
+        }
+        if let Err(err) = CgroupFilter::new(self) {
+            problems.push(err.to_string());
+        }
+        validation_result(problems)
     }
 }
 
 impl CgStats {
+    /// Read the state to calculate the CPU usage from. This is the
+    /// state of the previous run or, with `window`, the oldest
+    /// snapshot within the window. Expired snapshots are deleted if
+    /// `update_state` is set.
+    fn read_previous_state(
+        &self,
+        fs: &dyn CgStatsFs,
+        now: SystemTime,
+        update_state: bool,
+    ) -> Result<Option<State>, Box<dyn Error>> {
+        let window = match self.window {
+            Some(window) => window,
+            None => return Ok(read_state(fs, Path::new(&self.state_file))),
+        };
+        let mut before = None;
+        for (time, path) in self.snapshots(fs)? {
+            if now.duration_since(time).unwrap_or_default() > window {
+                if update_state {
+                    fs.remove_file(&path)?;
+                }
+            } else if before.is_none() {
+                before = read_state(fs, &path);
+            }
+        }
+        Ok(before)
+    }
+
+    /// Store the state for the next runs
+    fn write_state(&self, fs: &dyn CgStatsFs, state: &State) -> Result<(), Box<dyn Error>> {
+        let path = match self.window {
+            Some(_) => {
+                let secs = state.time.duration_since(UNIX_EPOCH)?.as_secs();
+                PathBuf::from(format!("{}.{secs}", self.state_file))
+            }
+            None => PathBuf::from(&self.state_file),
+        };
+        fs.write(&path, &toml::to_string(state)?)?;
+        Ok(())
+    }
+
+    /// Snapshots named `<state_file>.<seconds since epoch>`, oldest first
+    fn snapshots(&self, fs: &dyn CgStatsFs) -> Result<Vec<(SystemTime, PathBuf)>, Box<dyn Error>> {
+        let state_file = Path::new(&self.state_file);
+        let dir = match state_file.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let prefix = format!(
+            "{}.",
+            state_file
+                .file_name()
+                .ok_or_else(|| io::Error::other("state_file is not a file name"))?
+                .to_string_lossy()
+        );
+        let mut snapshots = vec![];
+        for file_name in fs.file_names(dir)? {
+            if let Some(secs) = file_name
+                .strip_prefix(&prefix)
+                .and_then(|secs| secs.parse::<u64>().ok())
+            {
+                snapshots.push((UNIX_EPOCH + Duration::from_secs(secs), dir.join(file_name)));
+            }
+        }
+        snapshots.sort();
+        Ok(snapshots)
+    }
+
+    /// Calculate the CPU usage and, if `update_state` is set, store the state for the next runs
     pub fn prepare_or_error(
         &self,
-        _global_config: &GlobalConfig,
+        global_config: &GlobalConfig,
+        fs: &dyn CgStatsFs,
+        update_state: bool,
     ) -> Result<PrepareReturn, Box<dyn Error>> {
-        let num_cpus = available_parallelism()?.get();
-        let now = read_cg_state()?;
+        let num_cpus = match self.cpu_count {
+            Some(cpu_count) => cpu_count,
+            None => available_cpus(),
+        }
+        .get();
+        let hierarchy = Hierarchy::new(self, fs)?;
+        let slices = self
+            .slices
+            .clone()
+            .unwrap_or_else(|| DEFAULT_SLICES.map(String::from).to_vec());
+        let filter = CgroupFilter::new(self)?;
+        let max_name_length = self.max_name_length.unwrap_or(DEFAULT_MAX_NAME_LENGTH);
+        let mut now = read_cg_state(
+            &hierarchy,
+            &slices,
+            &filter,
+            self.group_by,
+            self.include_slice_total,
+        )?;
 
-        let mut prepared_cg_stats = PreparedCgStats::default();
+        let mut prepared_cg_stats = PreparedCgStats {
+            wrap_names: self.wrap_names,
+            display_mode: self.display_mode,
+            threshold: self.threshold,
+            hide_when_empty: self.hide_when_empty,
+            round_up_nonzero: self.round_up_nonzero,
+            show_counts: self.show_counts,
+            bar_width: self.bar_width,
+            layout: self.layout,
+            since_boot: self.since_boot,
+            time_precision: self.time_precision,
+            percent_decimals: self.percent_decimals.unwrap_or(0),
+            ..Default::default()
+        };
 
-        if let Ok(before) = fs::read_to_string(&self.state_file)
-            .and_then(|s| toml::from_str::<State>(&s).map_err(io::Error::other))
-        {
-            let time_span = now.time.duration_since(before.time)?;
+        let previous = match self.since_boot {
+            true => Some(State::at_boot(&now)?),
+            false => self.read_previous_state(fs, now.time, update_state)?,
+        };
+        // If the clock went backwards since the previous run (e.g. NTP step),
+        // there is no usable delta, so start over as if this was the first run
+        let previous = previous.and_then(|before| {
+            let time_span = now.time.duration_since(before.time).ok()?;
+            Some((before, time_span))
+        });
+        if let Some((mut before, time_span)) = previous {
+            if self.min_time_span.is_some_and(|min| time_span < min) {
+                // Keep the previous state so that the next run gets a meaningful delta
+                prepared_cg_stats.time_span = Some(time_span);
+                prepared_cg_stats.too_fresh = true;
+                return Ok((Box::new(prepared_cg_stats), None));
+            }
             let treshold = self.threshold;
-            prepared_cg_stats.time_span = time_span;
-            prepared_cg_stats.users =
-                get_prepared_stats(&now.user, &before.user, time_span, num_cpus, treshold);
-            prepared_cg_stats.services =
-                get_prepared_stats(&now.system, &before.system, time_span, num_cpus, treshold);
-            prepared_cg_stats.max_name_width = prepared_cg_stats
-                .users
+            let no_stats = HashMap::new();
+            prepared_cg_stats.time_span = Some(time_span);
+            prepared_cg_stats.slices = slices
                 .iter()
-                .chain(prepared_cg_stats.services.iter())
-                .map(|s| s.name.len())
+                .map(|slice| {
+                    let (stats, total, stopped) = get_prepared_stats(
+                        &now.slices[slice],
+                        before.slices.get(slice).unwrap_or(&no_stats),
+                        time_span,
+                        num_cpus,
+                        treshold,
+                        self.track_lifecycle,
+                    );
+                    let slice_total =
+                        match (now.slice_totals.get(slice), before.slice_totals.get(slice)) {
+                            (Some(s2), Some(s1)) => Some(prepared_stat(
+                                SLICE_TOTAL_NAME,
+                                s2,
+                                s1.usage_usec,
+                                time_span,
+                                num_cpus,
+                            )),
+                            _ => None,
+                        };
+                    PreparedSlice {
+                        title: slice_title(slice),
+                        stats,
+                        total,
+                        stopped,
+                        slice_total,
+                        slice: slice.clone(),
+                    }
+                })
+                .collect();
+            if self.show_trend {
+                for prepared_slice in &mut prepared_cg_stats.slices {
+                    let previous_loads = before.loads.get(&prepared_slice.slice);
+                    for stat in &mut prepared_slice.stats {
+                        stat.previous_load =
+                            previous_loads.and_then(|loads| loads.get(&stat.name).copied());
+                    }
+                    now.loads.insert(
+                        prepared_slice.slice.clone(),
+                        prepared_slice
+                            .stats
+                            .iter()
+                            .map(|s| (s.name.clone(), s.load))
+                            .collect(),
+                    );
+                }
+                prepared_cg_stats.show_trend = true;
+            }
+            if let Some(history_length) = self.history_length.filter(|&l| l > 0) {
+                // Samples from the oldest to now, each pair of neighbours gives one point of the sparkline
+                let mut samples = std::mem::take(&mut before.history);
+                samples.push(Sample::new(&before));
+                samples.push(Sample::new(&now));
+                samples.drain(..samples.len().saturating_sub(history_length + 1));
+                for prepared_slice in &mut prepared_cg_stats.slices {
+                    add_history(
+                        &mut prepared_slice.stats,
+                        &samples,
+                        &prepared_slice.slice,
+                        num_cpus,
+                    );
+                }
+                prepared_cg_stats.sparkline_width = history_length;
+
+                // The next run will add its own sample, so keep one less
+                samples.pop();
+                samples.drain(..samples.len().saturating_sub(history_length - 1));
+                now.history = samples;
+            }
+            // After the trend and history, which look the statistics up by the real names
+            if self.anonymize_users {
+                anonymize_users(&mut prepared_cg_stats.slices, self.group_by);
+            }
+            for prepared_slice in &mut prepared_cg_stats.slices {
+                if !self.wrap_names {
+                    let names = prepared_slice
+                        .stats
+                        .iter_mut()
+                        .map(|stat| &mut stat.name)
+                        .chain(&mut prepared_slice.stopped);
+                    for name in names {
+                        *name = truncate_name(name, max_name_length).into_owned();
+                    }
+                }
+                let stats = &mut prepared_slice.stats;
+                if let Some(max_entries) = self.max_entries {
+                    sort_by_load(stats);
+                    stats.truncate(max_entries);
+                }
+                match self.sort_by {
+                    SortOrder::Name => stats.sort_by(|a, b| a.name.cmp(&b.name)),
+                    SortOrder::Load => sort_by_load(stats),
+                }
+            }
+            prepared_cg_stats.total_load = prepared_cg_stats.stats().map(|s| s.load).sum();
+            prepared_cg_stats.severity = prepared_cg_stats
+                .stats()
+                .map(|s| {
+                    Severity::of(
+                        s.load,
+                        global_config.progress_warn_threshold,
+                        global_config.progress_bad_threshold,
+                    )
+                })
+                .max()
+                .unwrap_or_default();
+            prepared_cg_stats.total_cpu_time = prepared_cg_stats.stats().map(|s| s.cpu_time).sum();
+            prepared_cg_stats.max_cpu_time = prepared_cg_stats
+                .rows()
+                .map(|s| s.cpu_time)
+                .max()
+                .unwrap_or_default();
+            prepared_cg_stats.cpu_time_width = prepared_cg_stats
+                .rows()
+                .map(|s| format_cpu_time(s.cpu_time).len())
+                .max()
+                .unwrap_or(0);
+            prepared_cg_stats.max_name_width = prepared_cg_stats
+                .rows()
+                .map(|s| s.name.chars().count())
                 .max()
                 .unwrap_or(0);
+            if self.wrap_names {
+                // Longer names get a line of their own
+                prepared_cg_stats.max_name_width =
+                    prepared_cg_stats.max_name_width.min(max_name_length);
+            }
+            if self.show_memory {
+                prepared_cg_stats.memory_width = prepared_cg_stats
+                    .rows()
+                    .filter_map(|s| s.memory.as_ref().map(|m| m.len()))
+                    .max()
+                    .unwrap_or(0);
+            }
+            if prepared_cg_stats.stats().any(|s| s.new) {
+                prepared_cg_stats.new_width = "(new)".len();
+            }
         }
-        fs::write(&self.state_file, toml::to_string(&now)?)?;
-        let min_width = INDENT_WIDTH + prepared_cg_stats.max_name_width + 12 + 5;
+        if update_state {
+            self.write_state(fs, &now)?;
+        }
+        let indent_width = global_config.indent_width();
+        let column_min_width = indent_width
+            + prepared_cg_stats.max_name_width
+            + prepared_cg_stats
+                .bar_width
+                .map_or(12, |bar_width| indent_width + 1 + bar_width)
+            + prepared_cg_stats.value_width()
+            + 1
+            + prepared_cg_stats.optional_columns_width();
+        let min_width = match prepared_cg_stats.layout {
+            SliceLayout::SideBySide => {
+                let columns = prepared_cg_stats.columns();
+                columns * column_min_width + columns.saturating_sub(1) * indent_width
+            }
+            SliceLayout::Stacked => column_min_width,
+        };
         Ok((
             Box::new(prepared_cg_stats),
             Some(Constraints {
@@ -83,62 +547,472 @@ impl CgStats {
 
 struct PreparedStat {
     name: String,
-    load: f64, // CPU load [0, 1]
+    load: f64,                  // CPU load [0, 1]
+    cpu_time: Duration,         // CPU time consumed during the time span
+    memory: Option<String>,     // Current memory usage (human readable)
+    history: Vec<Option<f64>>,  // CPU load in past runs (oldest first), None if unknown
+    previous_load: Option<f64>, // CPU load calculated by the previous run
+    new: bool,                  // Started since the previous run (with track_lifecycle)
+}
+
+impl PreparedStat {
+    /// Symbol showing whether the load went up or down since the previous run
+    fn trend(&self, percent_decimals: usize) -> char {
+        match self.previous_load {
+            // Compare the percentages as they are displayed
+            Some(previous) => {
+                match ((self.load - previous) * 100.0 * 10f64.powi(percent_decimals as i32)).round()
+                {
+                    delta if delta > 0.0 => '▲',
+                    delta if delta < 0.0 => '▼',
+                    _ => '–',
+                }
+            }
+            None => ' ',
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct PreparedCgStats {
-    time_span: Duration,
+    time_span: Option<Duration>, // None if there is no previous state (first run)
+    too_fresh: bool,             // Previous state is younger than min_time_span
+    since_boot: bool,            // The time span is the uptime rather than since a previous run
     max_name_width: usize,
-    users: Vec<PreparedStat>,
-    services: Vec<PreparedStat>,
+    wrap_names: bool,       // Longer names are printed on their own line
+    memory_width: usize,    // 0 if memory is not shown
+    new_width: usize,       // 0 if no Cgroup is marked as new
+    sparkline_width: usize, // 0 if the history is not shown
+    show_trend: bool,
+    time_precision: TimePrecision,
+    percent_decimals: usize,
+    display_mode: DisplayMode,
+    threshold: f64,
+    hide_when_empty: bool,
+    round_up_nonzero: bool,
+    show_counts: bool,
+    bar_width: Option<usize>, // Fixed width of the bars, if configured
+    layout: SliceLayout,
+    severity: Severity,       // Severity of the busiest listed Cgroup
+    cpu_time_width: usize,    // Width of the longest formatted CPU time
+    max_cpu_time: Duration,   // CPU time of the busiest listed Cgroup
+    total_cpu_time: Duration, // Sum of the CPU times of all listed Cgroups
+    total_load: f64,          // Sum of the loads of all listed Cgroups
+    slices: Vec<PreparedSlice>,
+}
+
+/// Statistics of the children of one slice
+struct PreparedSlice {
+    slice: String, // Name of the slice, e.g. user.slice
+    title: String, // Title of the section, e.g. Users
+    stats: Vec<PreparedStat>,
+    total: usize,         // Number of Cgroups with a measured CPU usage, listed or not
+    stopped: Vec<String>, // Cgroups gone since the previous run (with track_lifecycle)
+    slice_total: Option<PreparedStat>, // The slice itself (with include_slice_total)
+}
+
+impl PreparedCgStats {
+    /// Statistics from all slices
+    fn stats(&self) -> impl Iterator<Item = &PreparedStat> {
+        self.slices.iter().flat_map(|s| s.stats.iter())
+    }
+
+    /// Statistics from all slices including the totals of the slices, i.e. all printed rows
+    fn rows(&self) -> impl Iterator<Item = &PreparedStat> {
+        self.slices
+            .iter()
+            .flat_map(|s| s.stats.iter().chain(&s.slice_total))
+    }
+
+    /// Width of the CPU usage percentage without the percent sign
+    fn percent_width(&self) -> usize {
+        match self.percent_decimals {
+            0 => 3,
+            d => 3 + 1 + d,
+        }
+    }
+
+    /// Shown instead of the statistics if no Cgroup exceeds the threshold
+    fn below_threshold_message(&self) -> String {
+        // Round away floating point noise such as 7.000000000000001
+        let threshold = (self.threshold * 100.0 * 100.0).round() / 100.0;
+        format!("all tracked cgroups below {threshold}% threshold")
+    }
+
+    /// Width of the CPU usage column (percentage and/or CPU time)
+    fn value_width(&self) -> usize {
+        match self.display_mode {
+            DisplayMode::Percent => self.percent_width() + 1,
+            DisplayMode::CpuSeconds => self.cpu_time_width,
+            DisplayMode::Both => self.percent_width() + 2 + self.cpu_time_width,
+        }
+    }
+
+    /// CPU usage as shown in the CPU usage column, `pad`ded to align in a column
+    fn format_value(&self, load: f64, cpu_time: Duration, pad: bool) -> String {
+        let (percent_width, cpu_time_width) = match pad {
+            true => (self.percent_width(), self.cpu_time_width),
+            false => (0, 0),
+        };
+        let decimals = self.percent_decimals;
+        let rounded = format!("{:.decimals$}", load * 100.0);
+        let percent = match rounded.parse::<f64>() {
+            // Show that there is some usage instead of a misleading 0%
+            Ok(value) if self.round_up_nonzero && load > 0.0 && value == 0.0 => {
+                let smallest = format!("<{:.decimals$}", 10f64.powi(-(decimals as i32)));
+                format!("{smallest:>percent_width$}%")
+            }
+            _ => format!("{rounded:>percent_width$}%"),
+        };
+        let cpu_time = format!("{:>cpu_time_width$}", format_cpu_time(cpu_time));
+        match self.display_mode {
+            DisplayMode::Percent => percent,
+            DisplayMode::CpuSeconds => cpu_time,
+            DisplayMode::Both => format!("{percent} {cpu_time}"),
+        }
+    }
+
+    /// How much of the bar is filled for `stat`
+    /// CPU times are shown relative to the busiest Cgroup
+    fn bar_ratio(&self, stat: &PreparedStat) -> f64 {
+        match self.display_mode {
+            DisplayMode::CpuSeconds if self.max_cpu_time.is_zero() => 0.0,
+            DisplayMode::CpuSeconds => {
+                stat.cpu_time.as_secs_f64() / self.max_cpu_time.as_secs_f64()
+            }
+            DisplayMode::Percent | DisplayMode::Both => stat.load,
+        }
+    }
+
+    /// Number of sections with any listed Cgroup
+    fn columns(&self) -> usize {
+        self.slices
+            .iter()
+            .filter(|s| !s.stats.is_empty() || !s.stopped.is_empty())
+            .count()
+    }
+
+    /// Lines of the section of one slice: the title and one line per Cgroup
+    /// The lines of the Cgroups are indented relative to the title
+    /// Names longer than the name column are put on a line of their own if `wrap_names`
+    /// is set, otherwise they are truncated
+    fn slice_lines(
+        &self,
+        global_config: &GlobalConfig,
+        slice: &PreparedSlice,
+        layout: &BarLayout,
+        wrap_names: bool,
+    ) -> Vec<String> {
+        let indent = " ".repeat(global_config.indent_width());
+        let mut lines = vec![];
+        if slice.stats.is_empty() && slice.stopped.is_empty() {
+            return lines;
+        }
+        lines.push(match self.show_counts {
+            true => format!(
+                "{}: ({} shown / {} total)",
+                slice.title,
+                slice.stats.len(),
+                slice.total
+            ),
+            false => format!("{}:", slice.title),
+        });
+        for stat in slice.stats.iter().chain(&slice.slice_total) {
+            let sparkline = match self.sparkline_width {
+                0 => "".into(),
+                w => format!(" {:>w$}", format_sparkline(&stat.history)),
+            };
+            let memory = match self.memory_width {
+                0 => "".into(),
+                w => format!(" {:>w$}", stat.memory.as_deref().unwrap_or("")),
+            };
+            let trend = match self.show_trend {
+                true => format!("{} ", stat.trend(self.percent_decimals)),
+                false => "".into(),
+            };
+            let name = match wrap_names && stat.name.chars().count() > layout.name_width {
+                true => {
+                    let pad = " ".repeat(trend.chars().count());
+                    lines.push(format!("{indent}{pad}{}", stat.name));
+                    "".into()
+                }
+                false => layout.name(&stat.name),
+            };
+            let new = match stat.new {
+                true => " (new)",
+                false => "",
+            };
+            lines.push(format!(
+                "{indent}{trend}{name:<width$} {bar}{sparkline}{memory}{new}",
+                // The color reflects the load even if the bar is relative to the busiest Cgroup
+                bar = format_bar_with_value(
+                    global_config,
+                    layout.bar_width,
+                    self.bar_ratio(stat),
+                    colors::full_color(global_config, stat.load),
+                    &self.format_value(stat.load, stat.cpu_time, true),
+                ),
+                width = layout.name_width,
+            ));
+        }
+        for name in &slice.stopped {
+            lines.push(format!(
+                "{indent}{}{} (stopped){}",
+                colors::fg(color::LightBlack, global_config.color),
+                layout.name(name),
+                colors::reset(global_config.color)
+            ));
+        }
+        lines
+    }
+
+    /// Layout of the bars if the sections fit next to each other in `width`
+    fn side_by_side_layout(&self, width: usize, indent_width: usize) -> Option<BarLayout> {
+        let columns = self.columns();
+        if columns < 2 {
+            return None;
+        }
+        let column_width = width.saturating_sub((columns - 1) * indent_width) / columns;
+        let fixed_width = indent_width + 1 + self.value_width() + 1 + self.optional_columns_width();
+        let bar_width = self.bar_width.unwrap_or(MIN_BAR_WIDTH);
+        // Names are not truncated any more than in the stacked layout
+        (column_width >= fixed_width + self.max_name_width + bar_width).then(|| {
+            match self.bar_width {
+                Some(bar_width) => BarLayout {
+                    name_width: self.max_name_width,
+                    bar_width,
+                },
+                None => BarLayout::new(column_width, self.max_name_width, fixed_width),
+            }
+        })
+    }
+
+    /// Print the sections as columns next to each other
+    fn print_side_by_side(&self, global_config: &GlobalConfig, layout: &BarLayout) {
+        let indent = " ".repeat(global_config.indent_width());
+        let visible_width = |line: &str| colors::strip_escapes(line).chars().count();
+        let columns: Vec<_> = self
+            .slices
+            .iter()
+            // Wrapped names would shift the rows of one column against the others
+            .map(|slice| self.slice_lines(global_config, slice, layout, false))
+            .filter(|lines| !lines.is_empty())
+            .collect();
+        let widths: Vec<_> = columns
+            .iter()
+            .map(|lines| lines.iter().map(|l| visible_width(l)).max().unwrap_or(0))
+            .collect();
+        let rows = columns.iter().map(Vec::len).max().unwrap_or(0);
+        for row in 0..rows {
+            let line = columns
+                .iter()
+                .zip(&widths)
+                .map(|(lines, &width)| {
+                    let cell = lines.get(row).map(String::as_str).unwrap_or("");
+                    format!("{cell}{}", " ".repeat(width - visible_width(cell)))
+                })
+                .join(&indent);
+            println!("{indent}{}", line.trim_end());
+        }
+    }
+
+    /// Width of the enabled optional columns (trend, sparkline and memory)
+    fn optional_columns_width(&self) -> usize {
+        column_width(self.show_trend as usize)
+            + column_width(self.sparkline_width)
+            + column_width(self.memory_width)
+            + column_width(self.new_width)
+    }
 }
 
 #[async_trait]
 impl Component for PreparedCgStats {
     async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>) {
-        let secs = self.time_span.as_secs();
-        let rounded_time = if secs < 180 {
-            Duration::from_secs(secs)
-        } else {
-            Duration::from_secs((secs + 30) / 60 * 60)
+        let time_span = match self.time_span {
+            Some(time_span) => time_span,
+            None => {
+                println!("CPU usage: collecting CPU statistics, data available next run");
+                return;
+            }
         };
+        if self.too_fresh {
+            println!(
+                "CPU usage: data too fresh (previous sample {} ago), data available next run",
+                humantime::format_duration(Duration::from_secs(time_span.as_secs()))
+            );
+            return;
+        }
+        let secs = time_span.as_secs();
+        let rounded_time = round_duration(time_span, self.time_precision.granularity(secs));
+        let is_empty = self.stats().next().is_none();
+        if is_empty && self.hide_when_empty {
+            return;
+        }
         println!(
             "CPU usage in the past {}:{}",
             humantime::format_duration(rounded_time),
-            if self.users.len() + self.services.len() == 0 {
-                format!(" {}almost idle{}", color::Fg(color::Green), style::Reset)
+            if is_empty {
+                format!(
+                    " {}{}{}",
+                    colors::severity_color(global_config, Severity::Ok),
+                    self.below_threshold_message(),
+                    colors::reset(global_config.color)
+                )
             } else {
                 "".into()
             }
         );
-        let indent = " ".repeat(INDENT_WIDTH);
-        let width = width.unwrap_or(global_config.progress_width - INDENT_WIDTH);
-        let bar_width = width - INDENT_WIDTH - self.max_name_width - 1 - 5;
-        for (title, data) in [("Users", &self.users), ("Services", &self.services)] {
-            if !data.is_empty() {
-                println!("{indent}{title}:");
-            }
-            for stat in data {
-                println!(
-                    "{indent}{indent}{name:<width$} {percent:3.0}% {bar}",
-                    name = stat.name,
-                    bar = format_bar(global_config, bar_width, stat.load),
-                    percent = stat.load * 100.0,
-                    width = self.max_name_width,
-                );
+        let indent_width = global_config.indent_width();
+        let indent = " ".repeat(indent_width);
+        let width = width.unwrap_or(global_config.progress_width.saturating_sub(indent_width));
+        let side_by_side = match self.layout {
+            SliceLayout::SideBySide => self.side_by_side_layout(width, indent_width),
+            SliceLayout::Stacked => None,
+        };
+        match side_by_side {
+            Some(layout) => self.print_side_by_side(global_config, &layout),
+            None => {
+                let layout = match self.bar_width {
+                    // The terminal width is ignored so that the bars always have the same width
+                    Some(bar_width) => BarLayout {
+                        name_width: self.max_name_width,
+                        bar_width,
+                    },
+                    None => BarLayout::new(
+                        width,
+                        self.max_name_width,
+                        indent_width + 1 + self.value_width() + 1 + self.optional_columns_width(),
+                    ),
+                };
+                for slice in &self.slices {
+                    for line in self.slice_lines(global_config, slice, &layout, self.wrap_names) {
+                        println!("{indent}{line}");
+                    }
+                }
             }
         }
-        println!();
+        if self.stats().next().is_some() {
+            // Rounding errors can push the sum slightly over 100%
+            let total_load = self.total_load.min(1.0);
+            let total = self.format_value(total_load, self.total_cpu_time, false);
+            let total_color = match self.display_mode {
+                DisplayMode::CpuSeconds => "".into(),
+                DisplayMode::Percent | DisplayMode::Both => {
+                    colors::full_color(global_config, total_load)
+                }
+            };
+            println!(
+                "{indent}Total tracked: {total_color}{total}{}",
+                colors::reset(global_config.color),
+            );
+        }
     }
 
     default_prepare!();
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// The CPU usage is averaged since the previous state
+    /// Averaged since boot, it includes the current usage and is never stale
+    fn data_age(&self) -> Option<Duration> {
+        match self.since_boot {
+            true => None,
+            false => self.time_span,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.hide_when_empty
+            && self.time_span.is_some()
+            && !self.too_fresh
+            && self.stats().next().is_none()
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let stats_to_json = |stats: &Vec<PreparedStat>| {
+            stats
+                .iter()
+                .map(|s| {
+                    json!({"name": s.name, "load": s.load, "cpu_time": s.cpu_time.as_secs_f64(), "memory": s.memory, "history": s.history, "new": s.new})
+                })
+                .collect::<Vec<_>>()
+        };
+        json!({
+            "time_span": self.time_span.map(|t| t.as_secs_f64()),
+            "total_load": self.total_load,
+            "total_cpu_time": self.total_cpu_time.as_secs_f64(),
+            "slices": self
+                .slices
+                .iter()
+                .map(|s| (s.slice.clone(), stats_to_json(&s.stats).into()))
+                .collect::<serde_json::Map<_, _>>(),
+            "slice_totals": self
+                .slices
+                .iter()
+                .filter_map(|s| {
+                    let total = s.slice_total.as_ref()?;
+                    Some((s.slice.clone(), json!({"load": total.load, "cpu_time": total.cpu_time.as_secs_f64()})))
+                })
+                .collect::<serde_json::Map<_, _>>(),
+        })
+    }
+
+    fn to_markdown(&self) -> Option<String> {
+        let time_span = match self.time_span {
+            Some(time_span) if !self.too_fresh => time_span,
+            _ => return Some("CPU usage: data available next run\n".into()),
+        };
+        let mut markdown = format!(
+            "CPU usage in the past {}{}\n",
+            humantime::format_duration(Duration::from_secs(time_span.as_secs())),
+            if self.stats().next().is_none() {
+                format!(": {}", self.below_threshold_message())
+            } else {
+                "".into()
+            }
+        );
+        for PreparedSlice {
+            title,
+            stats,
+            slice_total,
+            ..
+        } in &self.slices
+        {
+            if stats.is_empty() {
+                continue;
+            }
+            markdown += &format!("\n| {title} | CPU usage |\n|---|---:|\n");
+            for stat in stats.iter().chain(slice_total) {
+                markdown += &format!(
+                    "| {} | {} |\n",
+                    stat.name.replace('|', "\\|"),
+                    self.format_value(stat.load, stat.cpu_time, false),
+                );
+            }
+        }
+        Some(markdown)
+    }
 }
 
 /// Statistics read from a single cgroup
 #[derive(Serialize, Deserialize)]
 struct CgStat {
     usage_usec: u64, // CPU usage
+    #[serde(default)]
+    memory_current: Option<u64>, // Memory usage in bytes
+}
+
+impl CgStat {
+    /// Add the usage of another Cgroup to this one
+    fn add(&mut self, other: &CgStat) {
+        self.usage_usec += other.usage_usec;
+        self.memory_current = match (self.memory_current, other.memory_current) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
 }
 
 /// Statistics from multiple cgroups read at certain time. CPU usage
@@ -147,146 +1021,959 @@ struct CgStat {
 #[derive(Serialize, Deserialize)]
 struct State {
     time: SystemTime,
-    user: HashMap<String, CgStat>,   // user.slice
-    system: HashMap<String, CgStat>, // system.slice
+    // Children of each slice
+    #[serde(default)]
+    slices: HashMap<String, HashMap<String, CgStat>>,
+    // Statistics of the slices themselves for include_slice_total
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    slice_totals: HashMap<String, CgStat>,
+    // CPU loads of the listed children of each slice for show_trend
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    loads: HashMap<String, HashMap<String, f64>>,
+    // Older samples for the sparkline (oldest first), missing in state files
+    // written by older versions or without history_length
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    history: Vec<Sample>,
+    // Children of user.slice and system.slice as stored by older versions
+    #[serde(default, skip_serializing)]
+    user: HashMap<String, CgStat>,
+    #[serde(default, skip_serializing)]
+    system: HashMap<String, CgStat>,
+}
+
+impl State {
+    /// The implicit state at boot time, when all Cgroups of `now` had used no CPU time yet
+    fn at_boot(now: &State) -> io::Result<Self> {
+        let zero = |stats: &HashMap<String, CgStat>| {
+            stats
+                .keys()
+                .map(|name| {
+                    let stat = CgStat {
+                        usage_usec: 0,
+                        memory_current: None,
+                    };
+                    (name.clone(), stat)
+                })
+                .collect()
+        };
+        Ok(State {
+            time: now.time - System::new().uptime()?,
+            slices: now
+                .slices
+                .iter()
+                .map(|(slice, stats)| (slice.clone(), zero(stats)))
+                .collect(),
+            slice_totals: zero(&now.slice_totals),
+            loads: HashMap::new(),
+            history: vec![],
+            user: HashMap::new(),
+            system: HashMap::new(),
+        })
+    }
+
+    /// Move statistics stored by older versions to `slices`
+    fn migrate(mut self) -> Self {
+        for (slice, stats) in [
+            ("user.slice", &mut self.user),
+            ("system.slice", &mut self.system),
+        ] {
+            if !stats.is_empty() {
+                self.slices.insert(slice.into(), std::mem::take(stats));
+            }
+        }
+        self
+    }
+}
+
+/// CPU usage of Cgroups from a past State
+#[derive(Serialize, Deserialize)]
+struct Sample {
+    time: SystemTime,
+    slices: HashMap<String, HashMap<String, u64>>, // usage_usec of the children of each slice
+}
+
+impl Sample {
+    fn new(state: &State) -> Self {
+        let usage = |stats: &HashMap<String, CgStat>| {
+            stats
+                .iter()
+                .map(|(name, stat)| (name.clone(), stat.usage_usec))
+                .collect()
+        };
+        Sample {
+            time: state.time,
+            slices: state
+                .slices
+                .iter()
+                .map(|(slice, stats)| (slice.clone(), usage(stats)))
+                .collect(),
+        }
+    }
 }
 
-fn full_color(ratio: f64) -> String {
-    match (ratio * 100.) as usize {
-        0..=75 => color::Fg(color::Green).to_string(),
-        76..=95 => color::Fg(color::Yellow).to_string(),
-        _ => color::Fg(color::Red).to_string(),
+/// Read a state written by a previous run, None if it is missing or invalid
+fn read_state(fs: &dyn CgStatsFs, path: &Path) -> Option<State> {
+    fs.read_to_string(path)
+        .ok()
+        .and_then(|s| toml::from_str::<State>(&s).ok())
+        .map(State::migrate)
+}
+
+/// Width of an optional column including the separating space
+fn column_width(width: usize) -> usize {
+    match width {
+        0 => 0,
+        w => w + 1,
     }
 }
 
-fn format_bar(global_config: &GlobalConfig, width: usize, full_ratio: f64) -> String {
-    let without_ends_width =
-        width - global_config.progress_suffix.len() - global_config.progress_prefix.len();
+/// Render CPU loads as a line of block characters, unknown loads are left blank
+fn format_sparkline(history: &[Option<f64>]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    history
+        .iter()
+        .map(|load| match load {
+            Some(load) => BLOCKS[(load.clamp(0.0, 1.0) * 7.0).round() as usize],
+            None => ' ',
+        })
+        .collect()
+}
 
-    let bar_full = ((without_ends_width as f64) * full_ratio.clamp(0.0, 1.0)).round() as usize;
-    let bar_empty = without_ends_width - bar_full;
-    let full_color = full_color(full_ratio);
+/// Sort by CPU load (highest first), ties are sorted by name
+fn sort_by_load(stats: &mut [PreparedStat]) {
+    stats.sort_by(|a, b| b.load.total_cmp(&a.load).then_with(|| a.name.cmp(&b.name)));
+}
+
+/// Fill in the history of each stat (children of `slice`) from consecutive samples
+fn add_history(stats: &mut [PreparedStat], samples: &[Sample], slice: &str, num_cpus: usize) {
+    for stat in stats {
+        stat.history = samples
+            .iter()
+            .tuple_windows()
+            .map(|(s1, s2)| {
+                let time_span = s2.time.duration_since(s1.time).ok()?;
+                let u1 = s1.slices.get(slice)?.get(&stat.name)?;
+                let u2 = s2.slices.get(slice)?.get(&stat.name)?;
+                Some(
+                    (*u2 as i64 - *u1 as i64) as f64
+                        / time_span.as_micros() as f64
+                        / num_cpus as f64,
+                )
+            })
+            .collect();
+    }
+}
 
-    [
-        global_config.progress_prefix.to_string(),
-        full_color,
-        global_config
-            .progress_full_character
-            .to_string()
-            .repeat(bar_full),
-        color::Fg(color::LightBlack).to_string(),
-        global_config
-            .progress_empty_character
-            .to_string()
-            .repeat(bar_empty),
-        style::Reset.to_string(),
-        global_config.progress_suffix.to_string(),
-    ]
-    .join("")
+/// Title of the section listing the children of `slice`
+fn slice_title(slice: &str) -> String {
+    match slice {
+        "user.slice" => "Users".into(),
+        "system.slice" => "Services".into(),
+        _ => {
+            let name = slice.strip_suffix(".slice").unwrap_or(slice);
+            let mut chars = name.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => slice.into(),
+            }
+        }
+    }
 }
 
 /// Calculate CPU usage from two states taken at different times. The
-/// result will include only Cgroups with CPU usage >= threshold.
+/// result will include only Cgroups with CPU usage >= threshold, together
+/// with the number of all Cgroups present in both states.
 fn get_prepared_stats(
     now: &HashMap<String, CgStat>,
     before: &HashMap<String, CgStat>,
     time_span: Duration,
     num_cpus: usize,
     threshold: f64,
-) -> Vec<PreparedStat> {
+    track_lifecycle: bool,
+) -> (Vec<PreparedStat>, usize, Vec<String>) {
     let mut stats = Vec::new();
+    let mut total = 0;
     for key in now.keys().sorted() {
-        if before.contains_key(key) {
-            let s1 = before.get(key).unwrap();
-            let s2 = now.get(key).unwrap();
-            let load = (s2.usage_usec as i64 - s1.usage_usec as i64) as f64
-                / time_span.as_micros() as f64
-                / num_cpus as f64;
-            if load >= threshold {
-                stats.push(PreparedStat {
-                    name: key.clone(),
-                    load,
-                });
+        let s2 = now.get(key).unwrap();
+        // A Cgroup started since the previous run consumed all its CPU time during the time span
+        let before_usage = match before.get(key) {
+            Some(s1) => s1.usage_usec,
+            None if track_lifecycle => 0,
+            None => continue,
+        };
+        total += 1;
+        let stat = PreparedStat {
+            new: !before.contains_key(key),
+            ..prepared_stat(key, s2, before_usage, time_span, num_cpus)
+        };
+        if stat.load >= threshold {
+            stats.push(stat);
+        }
+    }
+    let stopped = match track_lifecycle {
+        true => before
+            .keys()
+            .filter(|key| !now.contains_key(*key))
+            .sorted()
+            .cloned()
+            .collect(),
+        false => vec![],
+    };
+    (stats, total, stopped)
+}
+
+/// Replace the names of users other than the current one by user-N in the
+/// slices listing users. The users are numbered in the order of their names,
+/// so the numbers are stable within one run but not across runs.
+fn anonymize_users(slices: &mut [PreparedSlice], group_by: GroupBy) {
+    let current_user = users::get_current_username().and_then(|name| name.into_string().ok());
+    let mut user_slices: Vec<_> = slices
+        .iter_mut()
+        .filter(|s| matches!(group_by, GroupBy::User) || s.slice == "user.slice")
+        .collect();
+    // Grouped by user, Cgroups whose owner could not be determined keep their own names
+    let is_user = |name: &str| match group_by {
+        GroupBy::User => name.parse::<u32>().is_ok() || users::get_user_by_name(name).is_some(),
+        GroupBy::None => true,
+    };
+    let aliases: HashMap<String, String> = user_slices
+        .iter()
+        .flat_map(|s| s.stats.iter().map(|stat| &stat.name).chain(&s.stopped))
+        .filter(|name| Some(*name) != current_user.as_ref() && is_user(name))
+        .sorted()
+        .dedup()
+        .enumerate()
+        .map(|(i, name)| (name.clone(), format!("user-{}", i + 1)))
+        .collect();
+    for slice in &mut user_slices {
+        let names = slice
+            .stats
+            .iter_mut()
+            .map(|stat| &mut stat.name)
+            .chain(&mut slice.stopped);
+        for name in names {
+            if let Some(alias) = aliases.get(name) {
+                *name = alias.clone();
             }
         }
     }
-    stats
+}
+
+/// CPU usage of a Cgroup that used `before_usage` microseconds of CPU time `time_span` ago
+fn prepared_stat(
+    name: &str,
+    now: &CgStat,
+    before_usage: u64,
+    time_span: Duration,
+    num_cpus: usize,
+) -> PreparedStat {
+    PreparedStat {
+        name: name.to_string(),
+        load: (now.usage_usec as i64 - before_usage as i64) as f64
+            / time_span.as_micros() as f64
+            / num_cpus as f64,
+        cpu_time: Duration::from_micros(now.usage_usec.saturating_sub(before_usage)),
+        memory: now.memory_current.map(|m| ByteSize::b(m).to_string()),
+        history: vec![],
+        previous_load: None,
+        new: false,
+    }
+}
+
+/// Format CPU time in whole seconds, or milliseconds if it is less than a second
+fn format_cpu_time(cpu_time: Duration) -> String {
+    let rounded = match cpu_time.as_millis() {
+        ms if ms < 1000 => Duration::from_millis(ms as u64),
+        _ => Duration::from_secs(cpu_time.as_secs_f64().round() as u64),
+    };
+    humantime::format_duration(rounded).to_string()
+}
+
+/// Filesystem access of the component, i.e., reading the Cgroup hierarchy
+/// and reading and writing the state files
+pub trait CgStatsFs {
+    /// Read a whole file, e.g. `cpu.stat` of a Cgroup or a state file
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+    /// Write a whole file
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn exists(&self, path: &Path) -> bool;
+    /// Names of the subdirectories of `path`, i.e., the children of a Cgroup
+    fn child_dirs(&self, path: &Path) -> io::Result<Vec<String>>;
+    /// User ID of the owner of `path`
+    fn owner(&self, path: &Path) -> io::Result<u32>;
+    /// Names of all entries of the directory `path`
+    fn file_names(&self, path: &Path) -> io::Result<Vec<String>>;
+}
+
+/// The real filesystem
+pub struct RealFs;
+
+impl CgStatsFs for RealFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        // Readers not holding the lock (dry runs) never see a partially written state
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn child_dirs(&self, path: &Path) -> io::Result<Vec<String>> {
+        let mut dirs = vec![];
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                dirs.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        Ok(dirs)
+    }
+
+    fn owner(&self, path: &Path) -> io::Result<u32> {
+        Ok(fs::metadata(path)?.uid())
+    }
+
+    fn file_names(&self, path: &Path) -> io::Result<Vec<String>> {
+        fs::read_dir(path)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CgroupVersion {
+    V1,
+    V2,
+}
+
+/// Cgroup hierarchy from which the statistics are read
+struct Hierarchy<'a> {
+    fs: &'a dyn CgStatsFs,
+    /// Directory containing the slices, i.e. the root of the `cpuacct` controller with cgroup v1
+    root: PathBuf,
+    version: CgroupVersion,
+    read_memory: bool,
+}
+
+impl<'a> Hierarchy<'a> {
+    fn new(config: &CgStats, fs: &'a dyn CgStatsFs) -> Result<Self, Box<dyn Error>> {
+        let root = config
+            .cgroup_root
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CGROUP_ROOT));
+        let version = match config.cgroup_version {
+            Some(1) => CgroupVersion::V1,
+            Some(2) => CgroupVersion::V2,
+            Some(v) => {
+                return Err(io::Error::other(format!("Unsupported cgroup_version {v}")).into())
+            }
+            // Unified (v2) hierarchy has cgroup.controllers in its root
+            None => match fs.exists(&root.join("cgroup.controllers")) {
+                true => CgroupVersion::V2,
+                false => CgroupVersion::V1,
+            },
+        };
+        let root = match version {
+            // Most distributions mount cpu and cpuacct together and link cpuacct to it
+            CgroupVersion::V1 => ["cpu,cpuacct", "cpuacct"]
+                .iter()
+                .map(|controller| root.join(controller))
+                .find(|path| fs.exists(path))
+                .unwrap_or_else(|| root.join("cpuacct")),
+            CgroupVersion::V2 => root,
+        };
+        Ok(Hierarchy {
+            fs,
+            root,
+            version,
+            read_memory: config.show_memory,
+        })
+    }
+
+    /// Directory of the Cgroup given by `slice`
+    fn slice_path(&self, slice: &str) -> PathBuf {
+        self.root.join(slice)
+    }
+
+    /// Read statistics from a single Cgroup
+    fn read_cg_stat(&self, cg_path: &Path) -> Result<CgStat, Box<dyn Error>> {
+        match self.version {
+            CgroupVersion::V1 => read_cg_stat_v1(self.fs, cg_path),
+            CgroupVersion::V2 => read_cg_stat(self.fs, cg_path, self.read_memory),
+        }
+    }
+}
+
+/// Read statistics from a single Cgroup of the v1 `cpuacct` controller.
+/// Memory usage is not read from v1 hierarchies.
+fn read_cg_stat_v1(fs: &dyn CgStatsFs, cg_path: &Path) -> Result<CgStat, Box<dyn Error>> {
+    let usage_nsec = fs
+        .read_to_string(&cg_path.join("cpuacct.usage"))?
+        .trim()
+        .parse::<u64>()?;
+    Ok(CgStat {
+        usage_usec: usage_nsec / 1000,
+        memory_current: None,
+    })
 }
 
 /// Read statistics from a single Cgroup
-fn read_cg_stat(cg_path: &Path) -> Result<CgStat, Box<dyn Error>> {
+fn read_cg_stat(
+    fs: &dyn CgStatsFs,
+    cg_path: &Path,
+    read_memory: bool,
+) -> Result<CgStat, Box<dyn Error>> {
     let path = cg_path.join("cpu.stat");
-    let f = File::open(path.clone())?;
-    for line in BufReader::new(f).lines() {
-        let l = line?;
+    for l in fs.read_to_string(&path)?.lines() {
         let (key, value) = l
             .split_whitespace()
             .next_tuple()
             .ok_or_else(|| io::Error::other(format!("Reading fields from {path:?}")))?;
-        match (key, value.parse::<u64>()?) {
-            ("usage_usec", val) => return Ok(CgStat { usage_usec: val }),
-            _ => (),
+        if let ("usage_usec", val) = (key, value.parse::<u64>()?) {
+            // Memory controller may not be enabled for all Cgroups,
+            // in which case the memory usage is simply not shown
+            let memory_current = match read_memory {
+                true => fs
+                    .read_to_string(&cg_path.join("memory.current"))
+                    .ok()
+                    .and_then(|s| s.trim().parse::<u64>().ok()),
+                false => None,
+            };
+            return Ok(CgStat {
+                usage_usec: val,
+                memory_current,
+            });
+        }
+    }
+    Err(io::Error::other(format!("Missing usage_usec in {path:?}")).into())
+}
+
+/// Selection of Cgroups by the `include` and `exclude` options
+struct CgroupFilter {
+    include: Option<RegexSet>,
+    exclude: Option<RegexSet>,
+}
+
+impl CgroupFilter {
+    fn new(config: &CgStats) -> Result<Self, Box<dyn Error>> {
+        let compile = |patterns: &Option<Vec<String>>, option: &str| {
+            patterns
+                .as_ref()
+                .map(RegexSet::new)
+                .transpose()
+                .map_err(|e| io::Error::other(format!("Invalid regex in {option}: {e}")))
+        };
+        Ok(CgroupFilter {
+            include: compile(&config.include, "include")?,
+            exclude: compile(&config.exclude, "exclude")?,
+        })
+    }
+
+    /// Whether the Cgroup with the given (renamed) name should be listed
+    fn keeps(&self, name: &str) -> bool {
+        match (&self.include, &self.exclude) {
+            (Some(include), _) if include.is_match(name) => true,
+            (Some(_), _) => false,
+            (None, Some(exclude)) => !exclude.is_match(name),
+            (None, None) => true,
         }
     }
-    Err(io::Error::other("Missing {field} in {path}").into())
 }
 
 /// Read statistics from direct children of a Cgroup given by `slice`.
 /// The keys of the returned hash map are the names of Cgroups passed
-/// through the `rename_key` function.
-fn read_stats<F>(slice: &str, rename_key: F) -> Result<HashMap<String, CgStat>, Box<dyn Error>>
+/// through the `rename_key` function. Cgroups not kept by `filter` are skipped.
+/// Statistics of Cgroups renamed to the same key are summed if `sum` is set,
+/// otherwise such Cgroups keep their full names to stay apart.
+/// A slice that does not exist (e.g. machine.slice without VMs) has no children.
+fn read_stats<F>(
+    hierarchy: &Hierarchy,
+    slice: &str,
+    filter: &CgroupFilter,
+    sum: bool,
+    rename_key: F,
+) -> Result<HashMap<String, CgStat>, Box<dyn Error>>
 where
     F: Fn(&str) -> String,
 {
-    let mut stats = HashMap::new();
-    for entry in WalkDir::new(["/sys/fs/cgroup", slice].iter().collect::<PathBuf>())
-        .min_depth(1)
-        .max_depth(1)
-    {
-        let e = entry?;
-        if e.file_type().is_dir() {
-            let stat = read_cg_stat(e.path())?;
-            stats.insert(rename_key(&e.file_name().to_string_lossy()), stat);
+    let mut stats: HashMap<String, CgStat> = HashMap::new();
+    let slice_path = hierarchy.slice_path(slice);
+    let children = match hierarchy.fs.child_dirs(&slice_path) {
+        Ok(children) => children,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            eprintln!("cg_stats warning: skipping {}: {err}", slice_path.display());
+            vec![]
+        }
+        Err(err) => return Err(format!("Cannot read {}: {err}", slice_path.display()).into()),
+    };
+    for child in children {
+        let name = rename_key(&child);
+        if filter.keeps(&name) {
+            let cg_path = slice_path.join(&child);
+            // A single unreadable Cgroup (e.g. on locked-down hosts or when
+            // it disappears while being read) should not hide all the others
+            let stat = match hierarchy.read_cg_stat(&cg_path) {
+                Ok(stat) => stat,
+                Err(err) => {
+                    eprintln!("cg_stats warning: skipping {}: {err}", cg_path.display());
+                    continue;
+                }
+            };
+            match stats.entry(name) {
+                Entry::Occupied(mut entry) if sum => entry.get_mut().add(&stat),
+                Entry::Occupied(_) => {
+                    stats.insert(child, stat);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(stat);
+                }
+            }
         }
     }
     Ok(stats)
 }
 
-fn read_cg_state() -> Result<State, Box<dyn Error>> {
+/// Name of the user with the given ID, None if there is no such user
+fn user_name(uid: u32) -> Option<String> {
+    users::get_user_by_uid(uid).and_then(|user| user.name().to_str().map(String::from))
+}
+
+fn read_cg_state(
+    hierarchy: &Hierarchy,
+    slices: &[String],
+    filter: &CgroupFilter,
+    group_by: GroupBy,
+    include_slice_total: bool,
+) -> Result<State, Box<dyn Error>> {
     let mut state = State {
         time: SystemTime::now(),
+        slices: HashMap::new(),
+        slice_totals: HashMap::new(),
+        loads: HashMap::new(),
+        history: vec![],
         user: HashMap::new(),
         system: HashMap::new(),
     };
-    // Read statistics of system services and shorten too long names, e.g.,
-    // docker-dcd9a8c71b756de71a4a837c005840f84e0ed92574704ae1c89409c57980aaee.scope
-    let re = Regex::new(r"\.service|\.scope|\.slice")?;
-    state.system = read_stats("system.slice", |key| {
-        let name_no_suffix = re.replace(key, "");
-        let max_len = 23;
-        if name_no_suffix.len() <= max_len {
-            name_no_suffix.to_string()
+    lazy_static! {
+        static ref SUFFIX_REGEX: Regex = Regex::new(r"\.service|\.scope|\.slice").unwrap();
+        static ref UID_REGEX: Regex = Regex::new(r"^user-([0-9]+)\.slice$").unwrap();
+        static ref OWNER_REGEX: Regex = Regex::new(r"^user[-@]([0-9]+)\.(slice|service)$").unwrap();
+    }
+
+    for slice in slices {
+        let stats = if let GroupBy::User = group_by {
+            // Sum the statistics per user, who is given by the name of the
+            // user's slice or service manager or by the owner of the Cgroup
+            read_stats(hierarchy, slice, filter, true, |key| {
+                let uid = match OWNER_REGEX.captures(key) {
+                    Some(cap) => cap[1].parse::<u32>().ok(),
+                    None => hierarchy
+                        .fs
+                        .owner(&hierarchy.slice_path(slice).join(key))
+                        .ok(),
+                };
+                match uid {
+                    Some(uid) => user_name(uid).unwrap_or_else(|| uid.to_string()),
+                    None => key.to_owned(),
+                }
+            })?
+        } else if slice == "user.slice" {
+            // Read statistics of users and convert UIDs to user names
+            read_stats(hierarchy, slice, filter, false, |key| {
+                match UID_REGEX.captures(key) {
+                    Some(cap) => match cap[1].parse::<u32>() {
+                        Ok(uid) => user_name(uid).unwrap_or_else(|| key.to_owned()),
+                        Err(_) => key.to_owned(),
+                    },
+                    None => key.to_owned(),
+                }
+            })?
         } else {
-            let mut name = name_no_suffix.to_string();
-            name.truncate(max_len - 3);
-            name += "...";
-            name
-        }
-    })?;
-
-    // Read statistics of users and convert UIDs to user names
-    let re = Regex::new(r"^user-([0-9]+)\.slice$")?;
-    state.user = read_stats("user.slice", |key| match re.captures(key) {
-        Some(cap) => {
-            let uid = match cap[1].parse::<u32>() {
-                Ok(uid) => uid,
-                Err(_) => return key.to_owned(),
-            };
-            let user = users::get_user_by_uid(uid);
-            let username = user.as_ref().and_then(|u| u.name().to_str()).unwrap_or(key);
-            username.to_owned()
+            // Read statistics of services, too long names, e.g.,
+            // docker-dcd9a8c71b756de71a4a837c005840f84e0ed92574704ae1c89409c57980aaee.scope
+            // are shortened only when printed
+            read_stats(hierarchy, slice, filter, false, |key| {
+                SUFFIX_REGEX.replace(key, "").into_owned()
+            })?
+        };
+        state.slices.insert(slice.clone(), stats);
+        if include_slice_total {
+            let slice_path = hierarchy.slice_path(slice);
+            match hierarchy.read_cg_stat(&slice_path) {
+                Ok(stat) => {
+                    state.slice_totals.insert(slice.clone(), stat);
+                }
+                Err(err) => eprintln!("cg_stats warning: skipping {}: {err}", slice_path.display()),
+            }
         }
-        None => key.to_owned(),
-    })?;
+    }
     Ok(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    /// Files of a Cgroup hierarchy and of the state, directories exist implicitly
+    #[derive(Default)]
+    struct MockFs {
+        files: RefCell<BTreeMap<PathBuf, String>>,
+    }
+
+    impl MockFs {
+        /// A cgroup v2 hierarchy in /cg
+        fn new() -> Self {
+            let fs = MockFs::default();
+            fs.add("/cg/cgroup.controllers", "cpu memory");
+            fs
+        }
+
+        fn add(&self, path: &str, contents: &str) {
+            self.files
+                .borrow_mut()
+                .insert(PathBuf::from(path), contents.into());
+        }
+
+        /// A Cgroup in /cg that used `usage_usec` of CPU time
+        fn add_cgroup(&self, path: &str, usage_usec: u64) {
+            self.add(
+                &format!("/cg/{path}/cpu.stat"),
+                &format!("usage_usec {usage_usec}\nuser_usec {usage_usec}\nsystem_usec 0\n"),
+            );
+        }
+
+        fn add_state(&self, state: &State) {
+            self.add("/state", &toml::to_string(state).unwrap());
+        }
+
+        fn state(&self) -> Option<State> {
+            read_state(self, Path::new("/state"))
+        }
+
+        /// First component of the paths below `path`, only those with further components if `dirs`
+        fn entries(&self, path: &Path, dirs: bool) -> io::Result<Vec<String>> {
+            let files = self.files.borrow();
+            let entries: Vec<_> = files
+                .keys()
+                .filter_map(|file| file.strip_prefix(path).ok())
+                .filter(|rest| !dirs || rest.components().count() > 1)
+                .filter_map(|rest| rest.components().next())
+                .map(|name| name.as_os_str().to_string_lossy().into_owned())
+                .dedup()
+                .collect();
+            match self.exists(path) {
+                true => Ok(entries),
+                false => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+    }
+
+    impl CgStatsFs for MockFs {
+        fn read_to_string(&self, path: &Path) -> io::Result<String> {
+            self.files
+                .borrow()
+                .get(path)
+                .cloned()
+                .ok_or_else(|| io::ErrorKind::NotFound.into())
+        }
+
+        fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+            self.files
+                .borrow_mut()
+                .insert(path.to_owned(), contents.into());
+            Ok(())
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            match self.files.borrow_mut().remove(path) {
+                Some(_) => Ok(()),
+                None => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.files
+                .borrow()
+                .keys()
+                .any(|file| file.starts_with(path))
+        }
+
+        fn child_dirs(&self, path: &Path) -> io::Result<Vec<String>> {
+            self.entries(path, true)
+        }
+
+        fn owner(&self, _path: &Path) -> io::Result<u32> {
+            Ok(0)
+        }
+
+        fn file_names(&self, path: &Path) -> io::Result<Vec<String>> {
+            self.entries(path, false)
+        }
+    }
+
+    /// Configuration reading the mock hierarchy on a single CPU with additional `options`
+    fn config(options: &str) -> CgStats {
+        toml::from_str(&format!(
+            "state_file = \"/state\"\nthreshold = 0.1\ncgroup_root = \"/cg\"\ncpu_count = 1\n{options}"
+        ))
+        .unwrap()
+    }
+
+    /// State taken at `time` with the given CPU usage of the children of system.slice
+    fn state(time: SystemTime, services: &[(&str, u64)]) -> State {
+        let services = services
+            .iter()
+            .map(|&(name, usage_usec)| {
+                let stat = CgStat {
+                    usage_usec,
+                    memory_current: None,
+                };
+                (name.to_string(), stat)
+            })
+            .collect();
+        State {
+            time,
+            slices: HashMap::from([("system.slice".to_string(), services)]),
+            slice_totals: HashMap::new(),
+            loads: HashMap::new(),
+            history: vec![],
+            user: HashMap::new(),
+            system: HashMap::new(),
+        }
+    }
+
+    /// Prepare the component and return its JSON output
+    fn prepare(config: &CgStats, fs: &MockFs) -> serde_json::Value {
+        let (prepared, _) = config
+            .prepare_or_error(&GlobalConfig::default(), fs, true)
+            .unwrap();
+        prepared.to_json()
+    }
+
+    /// Names of the listed children of `slice`
+    fn names(json: &serde_json::Value, slice: &str) -> Vec<String> {
+        json["slices"][slice]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|stat| stat["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn threshold_as_ratio_or_percentage() {
+        let threshold = |value: &str| {
+            toml::from_str::<CgStats>(&format!("state_file = \"/state\"\nthreshold = {value}"))
+                .map(|config| config.threshold)
+        };
+        assert_eq!(threshold("0.01").unwrap(), 0.01);
+        assert_eq!(threshold("\"1%\"").unwrap(), 0.01);
+        assert_eq!(threshold("\" 2.5 % \"").unwrap(), 0.025);
+        assert_eq!(threshold("1").unwrap(), 1.0);
+        assert!(threshold("\"1\"").is_err());
+        assert!(threshold("\"one%\"").is_err());
+    }
+
+    #[test]
+    fn first_run_only_stores_the_state() {
+        let fs = MockFs::new();
+        fs.add_cgroup("system.slice/nginx.service", 1_000_000);
+
+        let json = prepare(&config(""), &fs);
+        assert!(json["time_span"].is_null());
+        let state = fs.state().expect("state written");
+        assert_eq!(state.slices["system.slice"]["nginx"].usage_usec, 1_000_000);
+        // The missing user.slice has no children
+        assert!(state.slices["user.slice"].is_empty());
+    }
+
+    #[test]
+    fn previous_state_from_the_future_starts_over() {
+        // The clock was set back by an hour since the previous run
+        let fs = MockFs::new();
+        let in_an_hour = SystemTime::now() + Duration::from_secs(3600);
+        fs.add_state(&state(in_an_hour, &[("nginx", 0)]));
+        fs.add_cgroup("system.slice/nginx.service", 5_000_000);
+
+        let json = prepare(&config(""), &fs);
+        assert!(json["time_span"].is_null());
+        // The state is refreshed so that the next run has a usable delta
+        let state = fs.state().unwrap();
+        assert!(state.time < in_an_hour);
+        assert_eq!(state.slices["system.slice"]["nginx"].usage_usec, 5_000_000);
+    }
+
+    #[test]
+    fn usage_since_boot_is_never_stale() {
+        let fs = MockFs::new();
+        fs.add_state(&state(
+            SystemTime::now() - Duration::from_secs(10),
+            &[("nginx", 0)],
+        ));
+        fs.add_cgroup("system.slice/nginx.service", 5_000_000);
+        let data_age = |config: &CgStats| {
+            let (prepared, _) = config
+                .prepare_or_error(&GlobalConfig::default(), &fs, false)
+                .unwrap();
+            prepared.data_age()
+        };
+        assert!(data_age(&config("")).is_some_and(|age| age >= Duration::from_secs(10)));
+        assert_eq!(data_age(&config("since_boot = true")), None);
+    }
+
+    #[test]
+    fn lists_only_cgroups_above_threshold() {
+        let fs = MockFs::new();
+        let ten_seconds_ago = SystemTime::now() - Duration::from_secs(10);
+        fs.add_state(&state(
+            ten_seconds_ago,
+            &[("busy", 0), ("idle", 0), ("barely", 0)],
+        ));
+        // 90%, 0% and 10% of the CPU during the 10 seconds (minus the time the test takes)
+        fs.add_cgroup("system.slice/busy.service", 9_000_000);
+        fs.add_cgroup("system.slice/idle.service", 0);
+        fs.add_cgroup("system.slice/barely.service", 1_100_000);
+
+        let json = prepare(&config(""), &fs);
+        assert_eq!(names(&json, "system.slice"), ["barely", "busy"]);
+        let load = json["slices"]["system.slice"][1]["load"].as_f64().unwrap();
+        assert!((0.85..=0.9).contains(&load), "load {load}");
+        // The new state is the baseline of the next run
+        assert!(fs.state().unwrap().time > ten_seconds_ago);
+    }
+
+    #[test]
+    fn skips_cgroup_without_cpu_stat() {
+        let fs = MockFs::new();
+        fs.add_state(&state(
+            SystemTime::now() - Duration::from_secs(10),
+            &[("nginx", 0), ("broken", 0)],
+        ));
+        fs.add_cgroup("system.slice/nginx.service", 5_000_000);
+        fs.add("/cg/system.slice/broken.service/cgroup.procs", "");
+
+        let json = prepare(&config(""), &fs);
+        assert_eq!(names(&json, "system.slice"), ["nginx"]);
+        assert!(!fs.state().unwrap().slices["system.slice"].contains_key("broken"));
+    }
+
+    #[test]
+    fn load_is_relative_to_cpu_count() {
+        let fs = MockFs::new();
+        fs.add_state(&state(
+            SystemTime::now() - Duration::from_secs(10),
+            &[("nginx", 0)],
+        ));
+        fs.add_cgroup("system.slice/nginx.service", 8_000_000);
+
+        let load = |json: serde_json::Value| json["slices"]["system.slice"][0]["load"].as_f64();
+        let one_cpu = load(prepare(&config(""), &fs)).unwrap();
+        fs.add_state(&state(
+            SystemTime::now() - Duration::from_secs(10),
+            &[("nginx", 0)],
+        ));
+        let config = CgStats {
+            cpu_count: NonZeroUsize::new(4),
+            ..config("")
+        };
+        let four_cpus = load(prepare(&config, &fs)).unwrap();
+        assert!((0.75..=0.8).contains(&one_cpu), "load {one_cpu}");
+        assert!((one_cpu / 4.0 - four_cpus).abs() < 0.01, "load {four_cpus}");
+    }
+
+    #[test]
+    fn anonymizes_only_users() {
+        let stat = |name: &str| {
+            let usage = CgStat {
+                usage_usec: 1,
+                memory_current: None,
+            };
+            prepared_stat(name, &usage, 0, Duration::from_secs(1), 1)
+        };
+        let current_user = users::get_current_username()
+            .and_then(|name| name.into_string().ok())
+            .unwrap();
+        let mut slices = [PreparedSlice {
+            slice: "system.slice".into(),
+            title: "Services".into(),
+            stats: vec![
+                stat("nobody"),
+                stat("4242"),
+                stat("cron.service"),
+                stat(&current_user),
+            ],
+            total: 4,
+            stopped: vec![],
+            slice_total: None,
+        }];
+        anonymize_users(&mut slices, GroupBy::User);
+        let names: Vec<_> = slices[0].stats.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["user-2", "user-1", "cron.service", &current_user]);
+    }
+
+    #[test]
+    fn truncates_long_service_names() {
+        let fs = MockFs::new();
+        let long_name = "docker-dcd9a8c71b756de71a4a837c005840f84e0ed92574704ae1c89409c57980aaee";
+        fs.add_state(&state(
+            SystemTime::now() - Duration::from_secs(10),
+            &[(long_name, 0)],
+        ));
+        fs.add_cgroup(&format!("system.slice/{long_name}.scope"), 5_000_000);
+
+        let json = prepare(&config("max_name_length = 10"), &fs);
+        assert_eq!(names(&json, "system.slice"), ["docker-..."]);
+
+        // With wrap_names, the names are kept whole
+        let json = prepare(&config("max_name_length = 10\nwrap_names = true"), &fs);
+        assert!(json["time_span"].is_number());
+        assert!(fs.state().unwrap().slices["system.slice"].contains_key(long_name));
+    }
+
+    #[test]
+    fn keeps_long_names_with_same_prefix_apart() {
+        let fs = MockFs::new();
+        let first = "docker-dcd9a8c71b756de71a4a837c005840f84e0ed92574704ae1c89409c57980aaee";
+        let second = "docker-dcd9a8c71b756de71a4a837c005840f84e0ed92574704ae1c89409c57980bbbb";
+        let previous = state(
+            SystemTime::now() - Duration::from_secs(10),
+            &[(first, 0), (second, 0)],
+        );
+        fs.add_state(&previous);
+        fs.add_cgroup(&format!("system.slice/{first}.scope"), 5_000_000);
+        fs.add_cgroup(&format!("system.slice/{second}.scope"), 2_000_000);
+
+        let json = prepare(&config("max_name_length = 10\nsort_by = \"load\""), &fs);
+        assert_eq!(names(&json, "system.slice"), ["docker-...", "docker-..."]);
+        let loads: Vec<_> = json["slices"]["system.slice"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|stat| (stat["load"].as_f64().unwrap() * 10.0).round())
+            .collect();
+        assert_eq!(loads, [5.0, 2.0]);
+        let state = fs.state().unwrap();
+        assert!(state.slices["system.slice"].contains_key(first));
+        assert!(state.slices["system.slice"].contains_key(second));
+
+        // The filter sees the full names
+        fs.add_state(&previous);
+        let json = prepare(&config("max_name_length = 10\ninclude = [\"bbbb$\"]"), &fs);
+        assert_eq!(names(&json, "system.slice"), ["docker-..."]);
+        assert_eq!(fs.state().unwrap().slices["system.slice"].len(), 1);
+    }
+}
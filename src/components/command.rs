@@ -0,0 +1,134 @@
+use std::os::unix::process::CommandExt;
+use std::process::Stdio;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use termion::color;
+use thiserror::Error;
+use tokio::process::Command as ProcessCommand;
+
+use crate::colors;
+use crate::component::{report_failure, Component};
+use crate::config::global_config::GlobalConfig;
+use crate::constants::INDENT_WIDTH;
+use crate::default_prepare;
+
+/// How much earlier than the component's timeout the command is killed
+const KILL_MARGIN: Duration = Duration::from_millis(100);
+
+/// Output of an arbitrary shell command
+#[derive(Debug, Deserialize)]
+pub struct Command {
+    /// Command line run by `/bin/sh -c`
+    shell: String,
+    /// Arguments available to the command line as `$1`, `$2`, etc.
+    #[serde(default)]
+    args: Vec<String>,
+    /// Indent each line of the output
+    #[serde(default)]
+    indent: bool,
+
+    // Taken from the common `timeout` option, see `with_command_timeout`
+    #[serde(skip)]
+    timeout: Option<Duration>,
+}
+
+#[derive(Error, Debug)]
+pub enum CommandError {
+    #[error("{shell:?} timed out after {}", humantime::format_duration(*.timeout))]
+    TimedOut { shell: String, timeout: Duration },
+
+    #[error("{shell:?} failed with {status}:\n{stderr}")]
+    Failed {
+        shell: String,
+        status: std::process::ExitStatus,
+        stderr: String,
+    },
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
+
+#[async_trait]
+impl Component for Command {
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
+        if let Err(err) = self.print_or_error(global_config).await {
+            println!(
+                "{}Command error: {}{}",
+                colors::fg(color::Red, global_config.color),
+                err.to_string().trim_end(),
+                colors::reset(global_config.color)
+            );
+            report_failure(err);
+        }
+    }
+    default_prepare!();
+}
+
+impl Command {
+    /// Kill the command once the component's timeout expires, so that it does not outlive rust-motd
+    pub fn with_command_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), CommandError> {
+        let mut command = std::process::Command::new("/bin/sh");
+        command
+            .arg("-c")
+            .arg(&self.shell)
+            .arg("rust-motd") // $0 of the command line
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // Own process group, so that the whole pipeline can be killed on timeout
+            .process_group(0);
+        let child = ProcessCommand::from(command).kill_on_drop(true).spawn()?;
+        let pid = child.id();
+        let output = child.wait_with_output();
+        let output = match self.timeout.or(global_config.default_timeout) {
+            Some(timeout) => {
+                // Finish before the runner gives up on the component, so that the error is shown
+                let deadline = timeout.saturating_sub(KILL_MARGIN);
+                match tokio::time::timeout(deadline, output).await {
+                    Ok(output) => output?,
+                    Err(_) => {
+                        if let Some(pid) = pid {
+                            // SAFETY: plain system call, the process group is ours
+                            unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+                        }
+                        return Err(CommandError::TimedOut {
+                            shell: self.shell,
+                            timeout,
+                        });
+                    }
+                }
+            }
+            None => output.await?,
+        };
+        if !output.status.success() {
+            return Err(CommandError::Failed {
+                shell: self.shell,
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            });
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stdout = match global_config.color {
+            true => stdout,
+            false => colors::strip_escapes(&stdout).into_owned().into(),
+        };
+        let indent = match self.indent {
+            true => " ".repeat(INDENT_WIDTH),
+            false => "".into(),
+        };
+        for line in stdout.lines() {
+            println!("{indent}{line}");
+        }
+
+        Ok(())
+    }
+}
@@ -4,9 +4,10 @@ use docker_api::models::ContainerSummary;
 use docker_api::opts::ContainerListOpts;
 use docker_api::{Docker as DockerAPI, Result as DockerResult};
 use std::collections::HashMap;
-use termion::{color, style};
+use termion::color;
 
-use crate::component::Component;
+use crate::colors;
+use crate::component::{print_error, Component};
 use crate::config::global_config::GlobalConfig;
 use crate::default_prepare;
 
@@ -16,12 +17,11 @@ pub struct Docker {
 
 #[async_trait]
 impl Component for Docker {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
         println!("Docker:");
-        self.print_or_error()
+        self.print_or_error(global_config)
             .await
-            .unwrap_or_else(|err| println!("Docker status error: {}", err));
-        println!();
+            .unwrap_or_else(|err| print_error("Docker status error", err));
     }
     default_prepare!();
 }
@@ -42,7 +42,10 @@ struct Container {
 }
 
 impl Docker {
-    pub async fn print_or_error(mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn print_or_error(
+        mut self,
+        global_config: &GlobalConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let docker = new_docker()?;
 
         // Get all containers from library and then filter them
@@ -76,9 +79,9 @@ impl Docker {
             println!(
                 "{indent}{color}Warning: Could not find Docker container `{docker_name}'{reset}",
                 indent = " ".repeat(INDENT_WIDTH),
-                color = color::Fg(color::Yellow),
+                color = colors::fg(color::Yellow, global_config.color),
                 docker_name = docker_name,
-                reset = style::Reset
+                reset = colors::reset(global_config.color)
             );
         }
 
@@ -92,11 +95,11 @@ impl Docker {
             for container in containers {
                 let status_color = match container.summary.state.as_deref() {
                     Some("Created") | Some("Restarting") | Some("Paused") | Some("Removing")
-                    | Some("Configured") => color::Fg(color::Yellow).to_string(),
-                    Some("Running") => color::Fg(color::Green).to_string(),
-                    Some("Exited") => color::Fg(color::LightBlack).to_string(),
-                    Some("Dead") => color::Fg(color::Red).to_string(),
-                    _ => color::Fg(color::White).to_string(),
+                    | Some("Configured") => colors::fg(color::Yellow, global_config.color),
+                    Some("Running") => colors::fg(color::Green, global_config.color),
+                    Some("Exited") => colors::fg(color::LightBlack, global_config.color),
+                    Some("Dead") => colors::fg(color::Red, global_config.color),
+                    _ => colors::fg(color::White, global_config.color),
                 };
                 println!(
                     "{indent}{name}: {padding}{color}{status}{reset}",
@@ -105,7 +108,7 @@ impl Docker {
                     padding = " ".repeat(max_len - container.name.len()),
                     color = status_color,
                     status = container.summary.status.unwrap_or(String::from("?")),
-                    reset = style::Reset,
+                    reset = colors::reset(global_config.color),
                 );
             }
         }
@@ -5,22 +5,29 @@ use regex::Regex;
 use serde::Deserialize;
 use thiserror::Error;
 
+use crate::colors;
 use crate::command::{BetterCommand, BetterCommandError};
-use crate::component::Component;
+use crate::component::{print_error, Component, Severity};
 use crate::config::global_config::GlobalConfig;
 use crate::default_prepare;
 
 #[derive(Debug, Deserialize)]
 pub struct Fail2Ban {
     jails: Vec<String>,
+    /// Print a single `jail: N banned` line per jail instead of the total and current bans
+    #[serde(default)]
+    compact: bool,
+    /// Number of currently banned IPs from which the count is printed in yellow
+    warn_bans: Option<u32>,
+    /// Number of currently banned IPs from which the count is printed in red
+    bad_bans: Option<u32>,
 }
 
 #[async_trait]
 impl Component for Fail2Ban {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error()
-            .unwrap_or_else(|err| println!("Fail2Ban error: {}", err));
-        println!();
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
+        self.print_or_error(global_config)
+            .unwrap_or_else(|err| print_error("Fail2Ban error", err));
     }
     default_prepare!();
 }
@@ -35,6 +42,9 @@ pub enum Fail2BanError {
     #[error(transparent)]
     BetterCommand(#[from] BetterCommandError),
 
+    #[error("Unexpected output of fail2ban-client for jail {jail:?}: missing {field:?}")]
+    UnexpectedOutput { jail: String, field: &'static str },
+
     #[error("Failed to parse int in output")]
     ParseInt(#[from] std::num::ParseIntError),
 
@@ -54,31 +64,68 @@ fn get_jail_status(jail: &str) -> Result<Entry, Fail2BanError> {
         .arg(jail)
         .check_status_and_get_output_string()?;
 
-    let total = TOTAL_REGEX.captures_iter(&output).next().unwrap()[1].parse::<u32>()?;
-    let current = CURRENT_REGEX.captures_iter(&output).next().unwrap()[1].parse::<u32>()?;
+    let capture = |regex: &Regex, field: &'static str| -> Result<u32, Fail2BanError> {
+        let captures = regex
+            .captures(&output)
+            .ok_or_else(|| Fail2BanError::UnexpectedOutput {
+                jail: jail.to_string(),
+                field,
+            })?;
+        Ok(captures[1].parse::<u32>()?)
+    };
+
+    let total = capture(&TOTAL_REGEX, "Total banned")?;
+    let current = capture(&CURRENT_REGEX, "Currently banned")?;
 
     Ok(Entry { total, current })
 }
 
 impl Fail2Ban {
-    pub fn print_or_error(self) -> Result<(), Fail2BanError> {
+    pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), Fail2BanError> {
         println!("Fail2Ban:");
 
-        for jail in self.jails {
-            let entry = get_jail_status(&jail)?;
-            println!(
-                concat!(
-                    "{indent}{jail}:\n",
-                    "{indent}{indent}Total bans:   {total}\n",
-                    "{indent}{indent}Current bans: {current}",
-                ),
-                jail = jail,
-                total = entry.total,
-                current = entry.current,
-                indent = " ".repeat(INDENT_WIDTH),
+        for jail in &self.jails {
+            let entry = get_jail_status(jail)?;
+            let current = format!(
+                "{}{}{}",
+                self.current_color(entry.current, global_config),
+                entry.current,
+                colors::reset(global_config.color)
             );
+            if self.compact {
+                println!(
+                    "{indent}{jail}: {current} banned",
+                    indent = " ".repeat(INDENT_WIDTH),
+                );
+            } else {
+                println!(
+                    concat!(
+                        "{indent}{jail}:\n",
+                        "{indent}{indent}Total bans:   {total}\n",
+                        "{indent}{indent}Current bans: {current}",
+                    ),
+                    jail = jail,
+                    total = entry.total,
+                    current = current,
+                    indent = " ".repeat(INDENT_WIDTH),
+                );
+            }
         }
 
         Ok(())
     }
+
+    /// Color of the number of currently banned IPs, empty if no threshold is configured
+    fn current_color(&self, current: u32, global_config: &GlobalConfig) -> String {
+        match (self.warn_bans, self.bad_bans) {
+            (None, None) => String::new(),
+            (_, Some(bad)) if current >= bad => {
+                colors::severity_color(global_config, Severity::Crit)
+            }
+            (Some(warn), _) if current >= warn => {
+                colors::severity_color(global_config, Severity::Warn)
+            }
+            _ => colors::severity_color(global_config, Severity::Ok),
+        }
+    }
 }
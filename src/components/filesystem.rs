@@ -5,13 +5,13 @@ use itertools::Itertools;
 use std::cmp;
 use std::iter;
 use systemstat::{Filesystem, Platform, System};
-use termion::{color, style};
 use thiserror::Error;
 
-use crate::component::{Component, Constraints, PrepareReturn};
+use crate::component::{print_error, Component, Constraints, PrepareReturn};
 use crate::config::global_config::GlobalConfig;
 use crate::constants::INDENT_WIDTH;
 use crate::default_prepare;
+use crate::progress_bar::format_bar;
 
 const HEADER: [&str; 6] = ["Filesystems", "Device", "Mount", "Type", "Used", "Total"];
 
@@ -29,11 +29,20 @@ impl Component for Filesystems {
             .unwrap_or((self, Some(Constraints { min_width: None })))
     }
 
-    // Print should never be called on a raw `Filesystems`
-    // Prepare should be called, returning a `PreparedFilesystems`
+    // Print is called on a raw `Filesystems` only if the preparation failed
+    // Otherwise, prepare returns a `PreparedFilesystems`
     async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>) {
-        let (prepared_filesystems, _) = self.prepare(global_config);
-        prepared_filesystems.print(global_config, width).await;
+        match self.prepare_or_error(global_config) {
+            Ok((prepared_filesystems, _)) => prepared_filesystems.print(global_config, width).await,
+            Err(err) => print_error("Filesystem error", err),
+        }
+    }
+
+    fn validate(&self, _global_config: &GlobalConfig) -> Result<(), Vec<String>> {
+        match self.mounts.is_empty() {
+            true => Err(vec![FilesystemsError::ConfigEmtpy.to_string()]),
+            false => Ok(()),
+        }
     }
 }
 
@@ -44,16 +53,14 @@ impl Component for Filesystems {
 struct PreparedFilesystems {
     column_sizes: Vec<usize>,
     entries: Vec<Entry>,
-    bar_width: usize,
+    bar_width: usize, // Including the progress prefix and suffix
 }
 
 #[async_trait]
 impl Component for PreparedFilesystems {
     async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error(global_config).unwrap_or_else(|err| {
-            println!("Filesystem error: {}", err);
-        });
-        println!();
+        self.print_or_error(global_config)
+            .unwrap_or_else(|err| print_error("Filesystem error", err));
     }
 
     default_prepare!();
@@ -64,9 +71,6 @@ pub enum FilesystemsError {
     #[error("Empty configuration for filesystems. Please remove the entire block to disable this component.")]
     ConfigEmtpy,
 
-    #[error("Could not find mount {mount_point:?}")]
-    MountNotFound { mount_point: String },
-
     #[error(transparent)]
     IO(#[from] std::io::Error),
 }
@@ -95,7 +99,10 @@ fn parse_into_entry(filesystem_name: String, mount: &Filesystem) -> Entry {
         fs_type: mount.fs_type.to_string(),
         used: ByteSize::b(used).to_string(),
         total: ByteSize::b(total).to_string(),
-        used_ratio: (used as f64) / (total as f64),
+        used_ratio: match total {
+            0 => 0.0,
+            total => (used as f64) / (total as f64),
+        },
     }
 }
 
@@ -134,21 +141,21 @@ impl Filesystems {
             .map(|fs| (fs.fs_mounted_on.clone(), fs))
             .collect();
 
-        let entries = self
+        // Mount points that are not mounted (e.g. removable media) are skipped
+        let entries: Vec<Entry> = self
             .mounts
             .into_iter()
-            .map(
-                |(filesystem_name, mount_point)| match mounts.get(&mount_point) {
-                    Some(mount) => Ok(parse_into_entry(filesystem_name, mount)),
-                    _ => Err(FilesystemsError::MountNotFound { mount_point }),
-                },
-            )
-            .collect::<Result<Vec<Entry>, FilesystemsError>>()?;
+            .filter_map(|(filesystem_name, mount_point)| {
+                mounts
+                    .get(&mount_point)
+                    .map(|mount| parse_into_entry(filesystem_name, mount))
+            })
+            .collect();
         let column_sizes = entries
             .iter()
             .map(|entry| {
                 vec![
-                    entry.filesystem_name.len() + INDENT_WIDTH,
+                    entry.filesystem_name.len() + global_config.indent_width(),
                     entry.dev.len(),
                     entry.mount_point.len(),
                     entry.fs_type.len(),
@@ -164,16 +171,16 @@ impl Filesystems {
                     .collect()
             });
 
-        // -2 because "Filesystems" does not count (it is not indented)
-        // and because zero indexed
-        let bar_width = column_sizes.iter().sum::<usize>() + (HEADER.len() - 2) * INDENT_WIDTH
+        // The bar spans the whole row except for its indentation
+        let bar_width = column_sizes.iter().sum::<usize>() + (HEADER.len() - 1) * INDENT_WIDTH
+            - global_config.indent_width()
             - global_config.progress_prefix.len()
             - global_config.progress_suffix.len();
         let fs_display_width =
             bar_width + global_config.progress_prefix.len() + global_config.progress_suffix.len();
 
         let prepared_filesystems = PreparedFilesystems {
-            bar_width,
+            bar_width: fs_display_width,
             column_sizes,
             entries,
         };
@@ -191,12 +198,13 @@ impl PreparedFilesystems {
         print_row(HEADER, &self.column_sizes);
 
         for entry in self.entries {
-            let bar_full = ((self.bar_width as f64) * entry.used_ratio) as usize;
-            let bar_empty = self.bar_width - bar_full;
-
             print_row(
                 [
-                    &[" ".repeat(INDENT_WIDTH), entry.filesystem_name].concat(),
+                    &[
+                        " ".repeat(global_config.indent_width()),
+                        entry.filesystem_name,
+                    ]
+                    .concat(),
                     &entry.dev[..],
                     &entry.mount_point[..],
                     &entry.fs_type[..],
@@ -206,31 +214,10 @@ impl PreparedFilesystems {
                 &self.column_sizes,
             );
 
-            let full_color = match (entry.used_ratio * 100.0) as usize {
-                0..=75 => color::Fg(color::Green).to_string(),
-                76..=95 => color::Fg(color::Yellow).to_string(),
-                _ => color::Fg(color::Red).to_string(),
-            };
-
             println!(
-                "{}",
-                [
-                    " ".repeat(INDENT_WIDTH),
-                    global_config.progress_prefix.to_string(),
-                    full_color,
-                    global_config
-                        .progress_full_character
-                        .to_string()
-                        .repeat(bar_full),
-                    color::Fg(color::LightBlack).to_string(),
-                    global_config
-                        .progress_empty_character
-                        .to_string()
-                        .repeat(bar_empty),
-                    style::Reset.to_string(),
-                    global_config.progress_suffix.to_string(),
-                ]
-                .join("")
+                "{}{}",
+                " ".repeat(global_config.indent_width()),
+                format_bar(global_config, self.bar_width, entry.used_ratio)
             );
         }
 
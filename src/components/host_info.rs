@@ -0,0 +1,109 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use systemstat::{IpAddr, Platform, System};
+use thiserror::Error;
+
+use crate::component::{print_error, Component};
+use crate::config::global_config::GlobalConfig;
+use crate::default_prepare;
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HostInfo {
+    #[serde(default)]
+    prefix: String,
+    #[serde(default = "default_true")]
+    show_hostname: bool,
+    #[serde(default = "default_true")]
+    show_ipv4: bool,
+    #[serde(default)]
+    show_ipv6: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum HostInfoError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
+
+#[async_trait]
+impl Component for HostInfo {
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
+        self.print_or_error(global_config)
+            .unwrap_or_else(|err| print_error("Host info error", err));
+    }
+    default_prepare!();
+}
+
+impl HostInfo {
+    pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), HostInfoError> {
+        let mut parts = Vec::new();
+        if self.show_hostname {
+            parts.push(hostname()?);
+        }
+        if self.show_ipv4 || self.show_ipv6 {
+            let addresses = self.addresses()?;
+            if addresses.is_empty() {
+                parts.push("no address".to_string());
+            } else {
+                parts.push(addresses.join(", "));
+            }
+        }
+
+        if self.prefix.is_empty() {
+            println!("{}", parts.join(" "));
+        } else {
+            println!(
+                "{} {}",
+                global_config.pad_label(&self.prefix),
+                parts.join(" ")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Non-loopback addresses of all interfaces, IPv4 first
+    /// Link-local IPv6 addresses are skipped since they exist on every interface
+    fn addresses(&self) -> Result<Vec<String>, HostInfoError> {
+        let networks = System::new().networks()?;
+        let mut interfaces: Vec<_> = networks.into_values().collect();
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut ipv4 = Vec::new();
+        let mut ipv6 = Vec::new();
+        for network in interfaces {
+            for addr in network.addrs {
+                match addr.addr {
+                    IpAddr::V4(ip) if self.show_ipv4 && !ip.is_loopback() => {
+                        ipv4.push(ip.to_string())
+                    }
+                    IpAddr::V6(ip)
+                        if self.show_ipv6
+                            && !ip.is_loopback()
+                            && (ip.segments()[0] & 0xffc0) != 0xfe80 =>
+                    {
+                        ipv6.push(ip.to_string())
+                    }
+                    _ => {}
+                }
+            }
+        }
+        ipv4.append(&mut ipv6);
+        Ok(ipv4)
+    }
+}
+
+/// Name of this machine as reported by gethostname(2)
+pub fn hostname() -> Result<String, std::io::Error> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for `buf.len()` bytes
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Ok(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
@@ -2,30 +2,41 @@ use async_trait::async_trait;
 use chrono::{Local, TimeZone};
 use humantime::format_duration;
 use last_rs::{get_logins, Enter, Exit, LastError};
+use serde::Deserialize;
 use std::collections::HashMap;
+use std::path::Path;
 use std::time::Duration;
-use termion::{color, style};
 use thiserror::Error;
 use time::error::Format as TimeFormatError;
 use time::error::IndeterminateOffset as TimeIndeterminateOffsetError;
 use time::error::InvalidFormatDescription as TimeInvalidFormatDescriptionError;
 
+use crate::colors;
 use crate::command::BetterCommandError;
-use crate::component::Component;
+use crate::component::{print_error, Component, Severity};
 use crate::config::global_config::GlobalConfig;
 use crate::constants::INDENT_WIDTH;
 use crate::default_prepare;
 
+const WTMP_PATH: &str = "/var/log/wtmp";
+
+#[derive(Debug, Deserialize)]
 pub struct LastLogin {
-    pub users: HashMap<String, usize>,
+    /// Hosts logins are expected from, logins from other hosts are shown in yellow
+    /// If empty, no login is highlighted
+    #[serde(default)]
+    known_hosts: Vec<String>,
+    /// Number of the most recent logins to show for each user
+    /// The last login of the current user if empty
+    #[serde(flatten)]
+    users: HashMap<String, usize>,
 }
 
 #[async_trait]
 impl Component for LastLogin {
     async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
         self.print_or_error(global_config)
-            .unwrap_or_else(|err| println!("Last login error: {}", err));
-        println!();
+            .unwrap_or_else(|err| print_error("Last login error", err));
     }
     default_prepare!();
 }
@@ -57,9 +68,23 @@ pub enum LastLoginError {
 fn format_entry(
     entry: &Enter,
     longest_location: usize,
-    time_format: &str,
+    known_hosts: &[String],
+    global_config: &GlobalConfig,
 ) -> Result<String, LastLoginError> {
     let location = format!("{:>width$}", entry.host, width = longest_location);
+    // Local logins have no host
+    let location = match known_hosts.is_empty()
+        || entry.host.is_empty()
+        || known_hosts.contains(&entry.host)
+    {
+        true => location,
+        false => format!(
+            "{}{}{}",
+            colors::severity_color(global_config, Severity::Warn),
+            location,
+            colors::reset(global_config.color)
+        ),
+    };
     let login_time = entry.login_time;
 
     let exit = match entry.exit {
@@ -72,12 +97,26 @@ fn format_entry(
         }
         _ => {
             let (colour, message) = match entry.exit {
-                Exit::StillLoggedIn => (color::Fg(color::Green).to_string(), "still logged in"),
-                Exit::Crash(_) => (color::Fg(color::Yellow).to_string(), "crash"),
-                Exit::Reboot(_) => (color::Fg(color::Yellow).to_string(), "down"),
+                Exit::StillLoggedIn => (
+                    colors::severity_color(global_config, Severity::Ok),
+                    "still logged in",
+                ),
+                Exit::Crash(_) => (
+                    colors::severity_color(global_config, Severity::Warn),
+                    "crash",
+                ),
+                Exit::Reboot(_) => (
+                    colors::severity_color(global_config, Severity::Warn),
+                    "down",
+                ),
                 Exit::Logout(_) => unreachable!(),
             };
-            format!("{}{}{}", colour, message, style::Reset)
+            format!(
+                "{}{}{}",
+                colour,
+                message,
+                colors::reset(global_config.color)
+            )
         }
     };
 
@@ -88,7 +127,7 @@ fn format_entry(
         // chrono DateTime
         login_time = Local
             .timestamp(login_time.unix_timestamp(), 0)
-            .format(time_format),
+            .format(&global_config.time_format),
         exit = exit,
         indent = " ".repeat(2 * INDENT_WIDTH),
     ))
@@ -98,19 +137,36 @@ impl LastLogin {
     pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), LastLoginError> {
         println!("Last Login:");
 
-        for (username, num_logins) in self.users {
+        // E.g. in containers, logins are not recorded
+        if !Path::new(WTMP_PATH).exists() {
+            println!(
+                "{}no logins recorded ({WTMP_PATH} does not exist)",
+                " ".repeat(INDENT_WIDTH)
+            );
+            return Ok(());
+        }
+        let logins = get_logins(WTMP_PATH)?;
+
+        let mut users = self.users;
+        if users.is_empty() {
+            if let Some(username) = users::get_current_username() {
+                users.insert(username.to_string_lossy().into_owned(), 1);
+            }
+        }
+
+        for (username, num_logins) in users {
             println!("{}{}:", " ".repeat(INDENT_WIDTH), username);
-            let entries = get_logins("/var/log/wtmp")?
-                .into_iter()
+            let entries = logins
+                .iter()
                 .filter(|entry| entry.user == username)
                 .take(num_logins)
-                .collect::<Vec<Enter>>();
+                .collect::<Vec<&Enter>>();
 
             let longest_location = entries.iter().map(|entry| entry.host.len()).max();
             match longest_location {
                 Some(longest_location) => {
                     let formatted_entries = entries.iter().map(|entry| {
-                        format_entry(entry, longest_location, &global_config.time_format)
+                        format_entry(entry, longest_location, &self.known_hosts, global_config)
                     });
                     for entry in formatted_entries {
                         match entry {
@@ -123,8 +179,8 @@ impl LastLogin {
                     "{indent}{color}No logins found for `{username}'{reset}",
                     indent = " ".repeat(2 * INDENT_WIDTH),
                     username = username,
-                    color = color::Fg(color::Red),
-                    reset = style::Reset,
+                    color = colors::severity_color(global_config, Severity::Crit),
+                    reset = colors::reset(global_config.color),
                 ),
             }
         }
@@ -1,22 +1,65 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
 use async_trait::async_trait;
-use chrono::Local;
+use chrono::{DateTime, Local};
+use chrono_tz::Tz;
 use serde::Deserialize;
+use serde_json::json;
 use thiserror::Error;
 
-use crate::component::Component;
+use crate::component::{print_error, validation_result, Component};
 use crate::config::global_config::GlobalConfig;
 use crate::default_prepare;
+use crate::rounding::{round_duration, Granularity};
 
 #[derive(Debug, Deserialize)]
-pub struct LastRun {}
+pub struct LastRun {
+    /// Text printed before the time (default "Last updated")
+    label: Option<String>,
+    /// Print how long ago the last run was instead of the absolute time
+    #[serde(default)]
+    relative: bool,
+    /// File whose modification time is taken as the time of the last run
+    source: Option<PathBuf>,
+    /// IANA time zone name (e.g. "Europe/Prague") used instead of the local time zone
+    timezone: Option<String>,
+    /// Unit to which the time is rounded with `relative` (default minutes)
+    duration_granularity: Option<Granularity>,
+}
 
 #[async_trait]
 impl Component for LastRun {
     async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
         self.print_or_error(global_config)
-            .unwrap_or_else(|err| println!("Last run error: {}", err));
+            .unwrap_or_else(|err| print_error("Last run error", err));
     }
     default_prepare!();
+
+    fn validate(&self, _global_config: &GlobalConfig) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+        if let Some(Err(err)) = self
+            .timezone
+            .as_ref()
+            .map(|timezone| timezone.parse::<Tz>())
+        {
+            problems.push(LastRunError::InvalidTimezone(err).to_string());
+        }
+        // Without a source, the last run is now, i.e. always "0s ago"
+        if self.relative && self.source.is_none() {
+            problems.push("relative requires source".to_string());
+        }
+        validation_result(problems)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({ "time": self.last_run_time().to_rfc3339() })
+    }
+
+    fn render_inline(&self, global_config: &GlobalConfig) -> Option<String> {
+        self.format(global_config).ok()
+    }
 }
 
 #[derive(Error, Debug)]
@@ -24,16 +67,76 @@ pub enum LastRunError {
     #[error(transparent)]
     ChronoParse(#[from] chrono::ParseError),
 
+    #[error("timezone: {0}")]
+    InvalidTimezone(String),
+
+    #[error("cannot read the modification time of {path:?}: {source}")]
+    Source {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
     #[error(transparent)]
     IO(#[from] std::io::Error),
 }
 
 impl LastRun {
+    /// Modification time of the source file or the current time if there is none
+    /// A source file that cannot be read is reported as a warning on stderr
+    fn last_run_time(&self) -> DateTime<Local> {
+        let path = match &self.source {
+            Some(path) => path,
+            None => return Local::now(),
+        };
+        match fs::metadata(path).and_then(|m| m.modified()) {
+            Ok(modified) => DateTime::<Local>::from(modified),
+            Err(source) => {
+                let err = LastRunError::Source {
+                    path: path.clone(),
+                    source,
+                };
+                eprintln!("Last run warning: {err}, using the current time");
+                Local::now()
+            }
+        }
+    }
+
     pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), LastRunError> {
-        println!(
-            "Last updated: {}",
-            Local::now().format(&global_config.time_format)
-        );
+        println!("{}", self.format(global_config)?);
         Ok(())
     }
+
+    /// The label followed by the time of the last run
+    fn format(&self, global_config: &GlobalConfig) -> Result<String, LastRunError> {
+        let label = global_config.pad_label(&format!(
+            "{}:",
+            self.label.as_deref().unwrap_or("Last updated")
+        ));
+        let last_run = self.last_run_time();
+        if self.relative {
+            let secs = (Local::now() - last_run).num_seconds().max(0) as u64;
+            let rounded_time = round_duration(
+                Duration::from_secs(secs),
+                self.duration_granularity.unwrap_or(Granularity::Minutes),
+            );
+            Ok(format!(
+                "{label} {} ago",
+                humantime::format_duration(rounded_time)
+            ))
+        } else {
+            let formatted = match &self.timezone {
+                Some(timezone) => {
+                    let tz = timezone
+                        .parse::<Tz>()
+                        .map_err(LastRunError::InvalidTimezone)?;
+                    last_run
+                        .with_timezone(&tz)
+                        .format(&global_config.time_format)
+                        .to_string()
+                }
+                None => last_run.format(&global_config.time_format).to_string(),
+            };
+            Ok(format!("{label} {formatted}"))
+        }
+    }
 }
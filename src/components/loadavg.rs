@@ -1,63 +1,393 @@
-use std::thread::available_parallelism;
+use std::collections::HashSet;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::thread;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use serde::Deserialize;
-use systemstat::{Platform, System};
-use termion::{color, style};
+use serde_json::json;
+use systemstat::{LoadAverage, Platform, System};
+use thiserror::Error;
 
-use crate::component::Component;
+use crate::colors::{self, ColorName, InvalidColorError};
+use crate::component::{
+    available_cpus, print_error, validation_result, Component, PrepareReturn, Severity,
+    MIN_BAR_WIDTH,
+};
 use crate::config::global_config::GlobalConfig;
-use crate::default_prepare;
+use crate::progress_bar::format_bar_with_value;
+
+/// How long to measure the CPU utilization for `show_utilization`
+const UTILIZATION_INTERVAL: Duration = Duration::from_millis(200);
 
 #[derive(Debug, Deserialize)]
 pub struct LoadAvg {
     prefix: String,
+    prefix_color: Option<String>,
     warn_treshold: Option<f32>,
     bad_treshold: Option<f32>,
+    warn_ratio: Option<f32>,
+    bad_ratio: Option<f32>,
+    #[serde(default)]
+    per_core: bool,
+    #[serde(default)]
+    show_cores: bool,
+    #[serde(default)]
+    show_utilization: bool,
+    #[serde(default)]
+    as_bar: bool,
+    #[serde(default)]
+    physical_cores: bool,
+    /// Color the load averages by a gradient up to `bad_treshold` instead of three bands
+    #[serde(default)]
+    gradient: bool,
+    /// Number of CPUs the thresholds are relative to (default: the CPUs available to rust-motd)
+    cpu_count: Option<NonZeroUsize>,
+
+    // Computed by `prepare`
+    #[serde(skip)]
+    severity: Severity,
 }
 
 #[async_trait]
 impl Component for LoadAvg {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error()
-            .unwrap_or_else(|err| println!("LoadAvg error: {}", err));
-        println!();
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>) {
+        self.print_or_error(global_config, width)
+            .unwrap_or_else(|err| print_error("LoadAvg error", err));
+    }
+    fn prepare(mut self: Box<Self>, _global_config: &GlobalConfig) -> PrepareReturn {
+        // Errors are printed by `print`
+        self.severity = self.get_severity().unwrap_or_default();
+        (self, None)
+    }
+
+    fn validate(&self, _global_config: &GlobalConfig) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+        if let Some(Err(err)) = self.prefix_color.as_ref().map(|c| c.parse::<ColorName>()) {
+            problems.push(LoadAvgError::from(err).to_string());
+        }
+        if let Err(err) = self.tresholds(self.num_cpus()) {
+            problems.push(err.to_string());
+        }
+        // Thresholds derived from the number of CPUs are not comparable with absolute ones
+        for (warn_name, warn, bad_name, bad) in [
+            (
+                "warn_treshold",
+                self.warn_treshold,
+                "bad_treshold",
+                self.bad_treshold,
+            ),
+            ("warn_ratio", self.warn_ratio, "bad_ratio", self.bad_ratio),
+        ] {
+            if let (Some(warn), Some(bad)) = (warn, bad) {
+                if warn >= bad {
+                    problems.push(format!(
+                        "{warn_name} ({warn}) must be lower than {bad_name} ({bad})"
+                    ));
+                }
+            }
+        }
+        validation_result(problems)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        match load_average() {
+            Ok(lavg) => json!({
+                "one": lavg.one,
+                "five": lavg.five,
+                "fifteen": lavg.fifteen,
+                "cores": self.num_cpus(),
+            }),
+            Err(_) => serde_json::Value::Null,
+        }
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn render_inline(&self, global_config: &GlobalConfig) -> Option<String> {
+        // Neither the bar nor the second line fit in a shared line
+        if self.as_bar || self.show_utilization {
+            return None;
+        }
+        self.format_loads(global_config).ok()
+    }
+
+    fn to_markdown(&self) -> Option<String> {
+        let lavg = load_average().ok()?;
+        Some(format!(
+            "| 1 min | 5 min | 15 min |\n|---:|---:|---:|\n| {:.2} | {:.2} | {:.2} |\n",
+            lavg.one, lavg.five, lavg.fifteen
+        ))
     }
-    default_prepare!();
 }
 
-impl LoadAvg {
-    pub fn print_or_error(self) -> Result<(), std::io::Error> {
-        let sys = System::new();
-        let lavg = sys.load_average()?;
-        let num_cpus = available_parallelism()?.get();
-        let warn_treshold = self.warn_treshold.unwrap_or(num_cpus as f32);
-        let bad_treshold = self.bad_treshold.unwrap_or((4 * num_cpus) as f32);
-
-        let color = |load| {
-            if load >= bad_treshold {
-                color::Fg(color::Red).to_string()
-            } else if load >= warn_treshold {
-                color::Fg(color::Yellow).to_string()
-            } else {
-                color::Fg(color::Green).to_string()
+#[derive(Error, Debug)]
+pub enum LoadAvgError {
+    #[error("{name} must be positive, got {value}")]
+    InvalidRatio { name: String, value: f32 },
+
+    #[error("prefix_color: {0}")]
+    InvalidColor(#[from] InvalidColorError),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
+
+/// Threshold given either as an absolute value or as a ratio of the number of CPUs
+/// The absolute value takes precedence
+fn get_treshold(
+    absolute: Option<f32>,
+    ratio: Option<f32>,
+    ratio_name: &str,
+    default_ratio: f32,
+    num_cpus: usize,
+) -> Result<f32, LoadAvgError> {
+    match (absolute, ratio) {
+        (Some(absolute), _) => Ok(absolute),
+        (None, Some(ratio)) if ratio <= 0. => Err(LoadAvgError::InvalidRatio {
+            name: ratio_name.to_string(),
+            value: ratio,
+        }),
+        (None, Some(ratio)) => Ok(ratio * num_cpus as f32),
+        (None, None) => Ok(default_ratio * num_cpus as f32),
+    }
+}
+
+/// Load average from `systemstat`, or parsed from /proc/loadavg if that fails,
+/// e.g. on some minimal or musl-based systems
+fn load_average() -> Result<LoadAverage, std::io::Error> {
+    System::new().load_average().or_else(|err| {
+        let loadavg = fs::read_to_string("/proc/loadavg").map_err(|_| err)?;
+        let mut fields = loadavg.split_whitespace().map(str::parse::<f32>);
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(Ok(one)), Some(Ok(five)), Some(Ok(fifteen))) => {
+                Ok(LoadAverage { one, five, fifteen })
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Cannot parse /proc/loadavg: {:?}", loadavg.trim()),
+            )),
+        }
+    })
+}
+
+/// Number of physical cores (without SMT siblings) according to /proc/cpuinfo
+/// None if cpuinfo does not describe the cores, e.g. on some ARM systems
+fn physical_core_count() -> Option<usize> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    let mut cores = HashSet::new();
+    let mut physical_id = None;
+    for line in cpuinfo.lines() {
+        match line.split_once(':').map(|(k, v)| (k.trim(), v.trim())) {
+            Some(("physical id", id)) => physical_id = Some(id),
+            Some(("core id", id)) => {
+                cores.insert((physical_id, id));
             }
+            _ => {}
+        }
+    }
+    Some(cores.len()).filter(|&count| count > 0)
+}
+
+impl LoadAvg {
+    fn num_cpus(&self) -> usize {
+        self.cpu_count.unwrap_or_else(available_cpus).get()
+    }
+
+    /// Warning and bad thresholds for the given number of available CPUs
+    fn tresholds(&self, num_cpus: usize) -> Result<(f32, f32), LoadAvgError> {
+        // The available CPUs may be restricted below the number of physical cores
+        let treshold_cpus = match self.physical_cores {
+            true => physical_core_count().map_or(num_cpus, |cores| cores.min(num_cpus)),
+            false => num_cpus,
         };
+        let warn_treshold = get_treshold(
+            self.warn_treshold,
+            self.warn_ratio,
+            "warn_ratio",
+            1.,
+            treshold_cpus,
+        )?;
+        let bad_treshold = get_treshold(
+            self.bad_treshold,
+            self.bad_ratio,
+            "bad_ratio",
+            4.,
+            treshold_cpus,
+        )?;
+        Ok((warn_treshold, bad_treshold))
+    }
 
-        println!(
-            "{} {}{:.2}{}, {}{:.2}{}, {}{:.2}{}",
-            self.prefix,
-            color(lavg.one),
-            lavg.one,
-            style::Reset,
-            color(lavg.five),
-            lavg.five,
-            style::Reset,
-            color(lavg.fifteen),
-            lavg.fifteen,
-            style::Reset
-        );
+    /// Severity of the worst of the displayed load averages
+    fn get_severity(&self) -> Result<Severity, LoadAvgError> {
+        let lavg = load_average()?;
+        let (warn_treshold, bad_treshold) = self.tresholds(self.num_cpus())?;
+        let displayed = match self.as_bar {
+            true => vec![lavg.one],
+            false => vec![lavg.one, lavg.five, lavg.fifteen],
+        };
+        Ok(displayed
+            .into_iter()
+            .map(|load| Severity::of(load as f64, warn_treshold as f64, bad_treshold as f64))
+            .max()
+            .unwrap_or_default())
+    }
+
+    /// The prefix padded to `label_width` and colored with `prefix_color`
+    fn colored_prefix(&self, global_config: &GlobalConfig) -> Result<String, LoadAvgError> {
+        let padded_prefix = global_config.pad_label(&self.prefix);
+        Ok(match &self.prefix_color {
+            Some(prefix_color) => format!(
+                "{}{}{}",
+                colors::fg(prefix_color.parse::<ColorName>()?, global_config.color),
+                padded_prefix,
+                colors::reset(global_config.color)
+            ),
+            None => padded_prefix,
+        })
+    }
+
+    /// Color of a load average, either by the thresholds or by `gradient`
+    fn load_color(
+        &self,
+        global_config: &GlobalConfig,
+        load: f32,
+        (warn_treshold, bad_treshold): (f32, f32),
+    ) -> String {
+        match self.gradient {
+            true => colors::gradient_color(global_config, (load / bad_treshold) as f64),
+            false => colors::threshold_color(
+                global_config,
+                load as f64,
+                warn_treshold as f64,
+                bad_treshold as f64,
+            ),
+        }
+    }
+
+    /// A load average colored by `load_color`, followed by the load per core if configured
+    fn format_load(
+        &self,
+        global_config: &GlobalConfig,
+        load: f32,
+        num_cpus: usize,
+        (warn_treshold, bad_treshold): (f32, f32),
+    ) -> String {
+        format!(
+            "{}{:.2}{}{}",
+            self.load_color(global_config, load, (warn_treshold, bad_treshold)),
+            load,
+            colors::reset(global_config.color),
+            self.format_per_core(load, num_cpus)
+        )
+    }
+
+    fn format_per_core(&self, load: f32, num_cpus: usize) -> String {
+        match self.per_core {
+            true => format!(" ({:.2}/core)", load / num_cpus as f32),
+            false => "".to_string(),
+        }
+    }
+
+    fn format_cores(&self, num_cpus: usize) -> String {
+        match self.show_cores {
+            true => format!(" ({num_cpus} cores)"),
+            false => "".to_string(),
+        }
+    }
+
+    /// The prefix followed by the three load averages
+    fn format_loads(&self, global_config: &GlobalConfig) -> Result<String, LoadAvgError> {
+        let lavg = load_average()?;
+        let num_cpus = self.num_cpus();
+        let tresholds = self.tresholds(num_cpus)?;
+        let format_load = |load| self.format_load(global_config, load, num_cpus, tresholds);
+        Ok(format!(
+            "{} {}, {}, {}{}",
+            self.colored_prefix(global_config)?,
+            format_load(lavg.one),
+            format_load(lavg.five),
+            format_load(lavg.fifteen),
+            self.format_cores(num_cpus),
+        ))
+    }
+
+    pub fn print_or_error(
+        self,
+        global_config: &GlobalConfig,
+        width: Option<usize>,
+    ) -> Result<(), LoadAvgError> {
+        if self.as_bar {
+            let lavg = load_average()?;
+            let num_cpus = self.num_cpus();
+            let tresholds = self.tresholds(num_cpus)?;
+            let bad_treshold = tresholds.1;
+            // One minute load average relative to bad_treshold, i.e. a full bar is "bad"
+            let value = format!(
+                "{:.2}{}{}",
+                lavg.one,
+                self.format_per_core(lavg.one, num_cpus),
+                self.format_cores(num_cpus)
+            );
+            // Other components' lines are as wide as the width plus their indentation
+            let indent_width = global_config.indent_width();
+            let line_width = width
+                .unwrap_or(global_config.progress_width.saturating_sub(indent_width))
+                + indent_width;
+            let bar_width = line_width
+                .saturating_sub(global_config.pad_label(&self.prefix).chars().count() + 1)
+                .saturating_sub(value.chars().count() + 1)
+                .max(MIN_BAR_WIDTH);
+            let bar = format_bar_with_value(
+                global_config,
+                bar_width,
+                (lavg.one / bad_treshold) as f64,
+                self.load_color(global_config, lavg.one, tresholds),
+                &value,
+            );
+            println!("{} {bar}", self.colored_prefix(global_config)?);
+        } else {
+            println!("{}", self.format_loads(global_config)?);
+        }
+
+        if self.show_utilization {
+            let measurement = System::new().cpu_load_aggregate()?;
+            thread::sleep(UTILIZATION_INTERVAL);
+            let cpu = measurement.done()?;
+            println!(
+                "CPU: {:.0}% user, {:.0}% sys, {}{:.0}% idle{}",
+                (cpu.user + cpu.nice) * 100.,
+                (cpu.system + cpu.interrupt) * 100.,
+                colors::full_color(global_config, 1. - cpu.idle as f64),
+                cpu.idle * 100.,
+                colors::reset(global_config.color),
+            );
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thresholds_follow_cpu_count() {
+        let load_avg = |options: &str| {
+            toml::from_str::<LoadAvg>(&format!("prefix = \"Load\"\n{options}")).unwrap()
+        };
+        // The same as when the number of CPUs cannot be determined
+        let one_cpu = load_avg("cpu_count = 1");
+        assert_eq!(one_cpu.tresholds(one_cpu.num_cpus()).unwrap(), (1.0, 4.0));
+        let four_cpus = load_avg("cpu_count = 4\nwarn_ratio = 0.5");
+        assert_eq!(
+            four_cpus.tresholds(four_cpus.num_cpus()).unwrap(),
+            (2.0, 16.0)
+        );
+        assert!(toml::from_str::<LoadAvg>("prefix = \"Load\"\ncpu_count = 0").is_err());
+    }
+}
@@ -1,27 +1,41 @@
 use async_trait::async_trait;
 use serde::Deserialize;
 use systemstat::{saturating_sub_bytes, Platform, System};
-use termion::{color, style};
 use thiserror::Error;
 
-use crate::component::Component;
+use crate::colors;
+use crate::component::{print_error, Component};
 use crate::config::global_config::GlobalConfig;
-use crate::constants::INDENT_WIDTH;
 use crate::default_prepare;
+use crate::progress_bar::{format_bar, format_bar_with_color};
 
 #[derive(Debug, Deserialize)]
 pub struct Memory {
     swap_pos: SwapPosition,
+    /// Title printed above the bars (default "Memory")
+    prefix: Option<String>,
+    /// Usage ratio from which the bars are yellow (default `progress_warn_threshold`)
+    warn_ratio: Option<f64>,
+    /// Usage ratio from which the bars are red (default `progress_bad_threshold`)
+    bad_ratio: Option<f64>,
 }
 
 #[async_trait]
 impl Component for Memory {
     async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>) {
         self.print_or_error(global_config, width)
-            .unwrap_or_else(|err| println!("Memory error: {}", err));
-        println!();
+            .unwrap_or_else(|err| print_error("Memory error", err));
     }
     default_prepare!();
+
+    fn validate(&self, global_config: &GlobalConfig) -> Result<(), Vec<String>> {
+        // The global thresholds are checked by themselves
+        if self.warn_ratio.is_none() && self.bad_ratio.is_none() {
+            return Ok(());
+        }
+        self.check_ratios(global_config)
+            .map_err(|err| vec![err.to_string()])
+    }
 }
 
 #[derive(Error, Debug)]
@@ -29,6 +43,9 @@ pub enum MemoryError {
     #[error("Could not find memory quantity {quantity:?}")]
     MemoryNotFound { quantity: String },
 
+    #[error("warn_ratio ({warn}) must be lower than bad_ratio ({bad})")]
+    InvalidRatios { warn: f64, bad: f64 },
+
     #[allow(dead_code)]
     #[error("Getting memory information is not supported on the current platform (see issue #20)")]
     UnsupportedPlatform,
@@ -85,7 +102,11 @@ impl MemoryUsage {
             name,
             used: used.to_string(),
             total: total.to_string(),
-            used_ratio: used.as_u64() as f64 / total.as_u64() as f64,
+            // There may be no swap at all
+            used_ratio: match total.as_u64() {
+                0 => 0.0,
+                total => used.as_u64() as f64 / total as f64,
+            },
         })
     }
 
@@ -100,81 +121,75 @@ impl MemoryUsage {
     }
 }
 
-fn format_bar(
-    global_config: &GlobalConfig,
+fn print_stacked(
+    entries: Vec<MemoryUsage>,
+    indent: &str,
     width: usize,
-    full_ratio: f64,
-    full_color: String,
-) -> String {
-    let without_ends_width =
-        width - global_config.progress_suffix.len() - global_config.progress_prefix.len();
-
-    let bar_full = ((without_ends_width as f64) * full_ratio) as usize;
-    let bar_empty = without_ends_width - bar_full;
-
-    [
-        global_config.progress_prefix.to_string(),
-        full_color,
-        global_config
-            .progress_full_character
-            .to_string()
-            .repeat(bar_full),
-        color::Fg(color::LightBlack).to_string(),
-        global_config
-            .progress_empty_character
-            .to_string()
-            .repeat(bar_empty),
-        style::Reset.to_string(),
-        global_config.progress_suffix.to_string(),
-    ]
-    .join("")
-}
-
-fn full_color(ratio: f64) -> String {
-    match (ratio * 100.) as usize {
-        0..=75 => color::Fg(color::Green).to_string(),
-        76..=95 => color::Fg(color::Yellow).to_string(),
-        _ => color::Fg(color::Red).to_string(),
+    bar: impl Fn(usize, f64) -> String,
+) {
+    for entry in entries {
+        println!("{}{}: {} / {}", indent, entry.name, entry.used, entry.total);
+        let bar = bar(width, entry.used_ratio);
+        println!("{indent}{bar}", indent = indent, bar = bar);
     }
 }
 
-fn print_stacked(entries: Vec<MemoryUsage>, width: usize, global_config: &GlobalConfig) {
-    for entry in entries {
-        println!(
-            "{}{}: {} / {}",
-            " ".repeat(INDENT_WIDTH),
-            entry.name,
-            entry.used,
-            entry.total
-        );
-        let full_color = full_color(entry.used_ratio);
-        let bar = format_bar(global_config, width, entry.used_ratio, full_color);
-        println!(
-            "{indent}{bar}",
-            indent = " ".repeat(INDENT_WIDTH),
-            bar = bar
+impl Memory {
+    /// Render a bar colored according to this component's ratios, or the global ones if not set
+    fn format_bar(&self, global_config: &GlobalConfig, width: usize, ratio: f64) -> String {
+        if self.warn_ratio.is_none() && self.bad_ratio.is_none() {
+            return format_bar(global_config, width, ratio);
+        }
+        let color = colors::threshold_color(
+            global_config,
+            ratio,
+            self.warn_ratio
+                .unwrap_or(global_config.progress_warn_threshold),
+            self.bad_ratio
+                .unwrap_or(global_config.progress_bad_threshold),
         );
+        format_bar_with_color(global_config, width, ratio, color)
+    }
+
+    /// The ratios must be ordered, also if only one of them overrides the global threshold
+    fn check_ratios(&self, global_config: &GlobalConfig) -> Result<(), MemoryError> {
+        let warn = self
+            .warn_ratio
+            .unwrap_or(global_config.progress_warn_threshold);
+        let bad = self
+            .bad_ratio
+            .unwrap_or(global_config.progress_bad_threshold);
+        match warn < bad {
+            true => Ok(()),
+            false => Err(MemoryError::InvalidRatios { warn, bad }),
+        }
     }
-}
 
-impl Memory {
     pub fn print_or_error(
         self,
         global_config: &GlobalConfig,
         width: Option<usize>,
     ) -> Result<(), MemoryError> {
         let sys = System::new();
-        let width = width.unwrap_or(global_config.progress_width - INDENT_WIDTH);
+        let indent = " ".repeat(global_config.indent_width());
+        let width = width.unwrap_or(
+            global_config
+                .progress_width
+                .saturating_sub(global_config.indent_width()),
+        );
+
+        self.check_ratios(global_config)?;
+        let bar = |width, ratio| self.format_bar(global_config, width, ratio);
 
         let ram_usage =
             MemoryUsage::get_by_name("RAM".to_string(), &sys, "MemAvailable", "MemTotal")?;
-        println!("Memory");
+        println!("{}", self.prefix.as_deref().unwrap_or("Memory"));
         match self.swap_pos {
-            SwapPosition::None => print_stacked(vec![ram_usage], width, global_config),
+            SwapPosition::None => print_stacked(vec![ram_usage], &indent, width, bar),
             SwapPosition::Below => {
                 let swap_usage =
                     MemoryUsage::get_by_name("Swap".to_string(), &sys, "SwapFree", "SwapTotal")?;
-                print_stacked(vec![ram_usage, swap_usage], width, global_config)
+                print_stacked(vec![ram_usage, swap_usage], &indent, width, bar)
             }
             SwapPosition::Beside => {
                 let swap_usage =
@@ -194,22 +209,18 @@ impl Memory {
                     swap_usage.name, swap_usage.used, swap_usage.total
                 );
                 println!(
-                    "{}{ram_label:padding$}{spacing}{swap_label}",
-                    " ".repeat(INDENT_WIDTH),
+                    "{indent}{ram_label:padding$}{spacing}{swap_label}",
+                    indent = indent,
                     ram_label = ram_label,
                     padding = bar_width,
                     spacing = spacing,
                     swap_label = swap_label
                 );
-                let bar_color = full_color(ram_usage.used_ratio);
-                let ram_bar = format_bar(global_config, bar_width, ram_usage.used_ratio, bar_color);
-
-                let bar_color = full_color(swap_usage.used_ratio);
-                let swap_bar =
-                    format_bar(global_config, bar_width, swap_usage.used_ratio, bar_color);
+                let ram_bar = bar(bar_width, ram_usage.used_ratio);
+                let swap_bar = bar(bar_width, swap_usage.used_ratio);
                 println!(
                     "{indent}{ram}{spacing}{swap}",
-                    indent = " ".repeat(INDENT_WIDTH),
+                    indent = indent,
                     ram = ram_bar,
                     spacing = spacing,
                     swap = swap_bar
@@ -1,13 +1,19 @@
 pub mod banner;
 pub mod cg_stats;
+pub mod command;
 pub mod docker;
 pub mod fail_2_ban;
 pub mod filesystem;
+pub mod host_info;
 pub mod last_login;
 pub mod last_run;
 pub mod loadavg;
 pub mod memory;
+pub mod processes;
 pub mod service_status;
 pub mod ssl_certs;
+pub mod temperatures;
+pub mod time_sync;
 pub mod uptime;
+pub mod users;
 pub mod weather;
@@ -0,0 +1,262 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::colors;
+use crate::component::{
+    available_cpus, print_error, BarLayout, Component, Constraints, PrepareReturn,
+};
+use crate::config::global_config::GlobalConfig;
+use crate::default_prepare;
+use crate::progress_bar::format_bar_with_value;
+use crate::state_lock::lock_state_file;
+
+/// How long to measure the CPU usage if there is no previous state
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+fn default_count() -> usize {
+    5
+}
+
+/// Processes with the highest CPU usage
+#[derive(Debug, Deserialize)]
+pub struct Processes {
+    /// File where to store the CPU times of the processes needed by the next run
+    /// Without it, the CPU usage is measured for a short moment
+    state_file: Option<String>,
+    /// Number of processes to show
+    #[serde(default = "default_count")]
+    count: usize,
+}
+
+#[derive(Error, Debug)]
+pub enum ProcessesError {
+    #[error("Cannot determine the clock ticks per second")]
+    ClockTicks,
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+
+    #[error(transparent)]
+    TomlSerialize(#[from] toml::ser::Error),
+}
+
+/// CPU time of all processes at a certain time
+#[derive(Serialize, Deserialize)]
+struct State {
+    time: SystemTime,
+    processes: Vec<ProcStat>,
+}
+
+/// Statistics read from `/proc/<pid>/stat`
+#[derive(Serialize, Deserialize)]
+struct ProcStat {
+    pid: u32,
+    #[serde(skip)]
+    name: String,
+    /// Distinguishes processes with a reused PID
+    start_time: u64,
+    /// User and system CPU time in clock ticks
+    cpu_ticks: u64,
+}
+
+#[async_trait]
+impl Component for Processes {
+    fn prepare(self: Box<Self>, global_config: &GlobalConfig) -> PrepareReturn {
+        match self.prepare_or_error(global_config) {
+            Ok(prepared) => prepared,
+            // Printed in place of the output by `print`
+            Err(err) => (Box::new(PreparedProcesses::Failed(err)), None),
+        }
+    }
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>) {
+        self.prepare(global_config)
+            .0
+            .print(global_config, width)
+            .await
+    }
+}
+
+impl Processes {
+    fn prepare_or_error(
+        &self,
+        global_config: &GlobalConfig,
+    ) -> Result<PrepareReturn, ProcessesError> {
+        // SAFETY: plain system call
+        let ticks_per_sec = match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+            ticks if ticks > 0 => ticks as f64,
+            _ => return Err(ProcessesError::ClockTicks),
+        };
+        let num_cpus = available_cpus().get();
+
+        // Held until the new state is written
+        let lock = match &self.state_file {
+            Some(path) if !global_config.dry_run => {
+                let lock = lock_state_file(path)?;
+                if lock.is_none() {
+                    eprintln!(
+                        "processes warning: {path} is locked by another instance, not updating it"
+                    );
+                }
+                lock
+            }
+            _ => None,
+        };
+        let previous = self
+            .state_file
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|state| toml::from_str::<State>(&state).ok());
+        let previous = match previous {
+            Some(previous) => previous,
+            None => {
+                let previous = read_processes()?;
+                thread::sleep(SAMPLE_INTERVAL);
+                previous
+            }
+        };
+        let now = read_processes()?;
+
+        let time_span = now.time.duration_since(previous.time).unwrap_or_default();
+        let mut loads = vec![];
+        if !time_span.is_zero() {
+            for process in &now.processes {
+                let before = previous
+                    .processes
+                    .iter()
+                    .find(|p| p.pid == process.pid && p.start_time == process.start_time);
+                // Processes started since the previous state used all their CPU time in the time span
+                let ticks =
+                    process.cpu_ticks - before.map_or(0, |p| p.cpu_ticks.min(process.cpu_ticks));
+                let load = ticks as f64 / ticks_per_sec / time_span.as_secs_f64() / num_cpus as f64;
+                loads.push((format!("{} ({})", process.name, process.pid), load));
+            }
+        }
+        loads.sort_by(|a, b| b.1.total_cmp(&a.1));
+        loads.truncate(self.count);
+
+        if let (Some(path), Some(_)) = (&self.state_file, &lock) {
+            fs::write(path, toml::to_string(&now)?)?;
+        }
+
+        let prepared = PreparedProcesses::Loads(loads);
+        let min_width = prepared.min_width();
+        Ok((
+            Box::new(prepared),
+            Some(Constraints {
+                min_width: Some(min_width),
+            }),
+        ))
+    }
+}
+
+/// Read the CPU times of all processes
+/// Processes that exit while being read are skipped
+fn read_processes() -> Result<State, std::io::Error> {
+    let time = SystemTime::now();
+    let mut processes = vec![];
+    for entry in fs::read_dir("/proc")? {
+        let Some(pid) = entry?
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if let Some(process) = fs::read_to_string(format!("/proc/{pid}/stat"))
+            .ok()
+            .and_then(|stat| parse_stat(pid, &stat))
+        {
+            processes.push(process);
+        }
+    }
+    Ok(State { time, processes })
+}
+
+/// Parse `/proc/<pid>/stat`, see proc(5)
+fn parse_stat(pid: u32, stat: &str) -> Option<ProcStat> {
+    // The name may contain spaces and parentheses, so it ends at the last parenthesis
+    let (name, rest) = stat.split_once('(')?.1.rsplit_once(')')?;
+    // Fields from the third one (state) on
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    let start_time = fields.get(19)?.parse().ok()?;
+    Some(ProcStat {
+        pid,
+        name: name.to_string(),
+        start_time,
+        cpu_ticks: utime + stime,
+    })
+}
+
+pub enum PreparedProcesses {
+    /// Names and CPU usage (relative to all CPUs) of the busiest processes
+    Loads(Vec<(String, f64)>),
+    Failed(ProcessesError),
+}
+
+impl PreparedProcesses {
+    fn name_width(&self) -> usize {
+        match self {
+            PreparedProcesses::Loads(loads) => loads
+                .iter()
+                .map(|(name, _)| name.chars().count())
+                .max()
+                .unwrap_or(0),
+            PreparedProcesses::Failed(_) => 0,
+        }
+    }
+
+    fn min_width(&self) -> usize {
+        // Name, percentage and the smallest useful bar
+        self.name_width() + 1 + VALUE_WIDTH + 1 + 12
+    }
+}
+
+/// Width of the CPU usage, e.g. ` 12%`
+const VALUE_WIDTH: usize = 4;
+
+#[async_trait]
+impl Component for PreparedProcesses {
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>) {
+        let loads = match self.as_ref() {
+            PreparedProcesses::Loads(loads) => loads,
+            PreparedProcesses::Failed(err) => {
+                print_error("Processes error", err);
+                return;
+            }
+        };
+        println!("Top processes:");
+        let indent = " ".repeat(global_config.indent_width());
+        if loads.is_empty() {
+            println!("{indent}no processes");
+            return;
+        }
+        let width = width.unwrap_or(
+            global_config
+                .progress_width
+                .saturating_sub(global_config.indent_width()),
+        );
+        let layout = BarLayout::new(width, self.name_width(), 1 + VALUE_WIDTH + 1);
+        for (name, load) in loads {
+            println!(
+                "{indent}{:<width$} {}",
+                layout.name(name),
+                format_bar_with_value(
+                    global_config,
+                    layout.bar_width,
+                    *load,
+                    colors::full_color(global_config, *load),
+                    &format!("{:>3.0}%", load * 100.0),
+                ),
+                width = layout.name_width,
+            );
+        }
+    }
+    default_prepare!();
+}
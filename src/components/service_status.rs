@@ -1,11 +1,12 @@
 use async_trait::async_trait;
 use itertools::Itertools;
 use std::collections::HashMap;
-use termion::{color, style};
+use std::path::Path;
 use thiserror::Error;
 
+use crate::colors;
 use crate::command::{BetterCommand, BetterCommandError};
-use crate::component::Component;
+use crate::component::{print_error, Component, Severity};
 use crate::config::global_config::GlobalConfig;
 use crate::constants::INDENT_WIDTH;
 use crate::default_prepare;
@@ -20,24 +21,30 @@ pub struct UserServiceStatus {
 
 #[async_trait]
 impl Component for ServiceStatus {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
         println!("System Services:");
-        print_or_error(&self.services, false)
-            .unwrap_or_else(|err| println!("Service status error: {}", err));
-        println!();
+        print_or_error(&self.services, false, global_config)
+            .unwrap_or_else(|err| print_error("Service status error", err));
     }
     default_prepare!();
+
+    fn validate(&self, _global_config: &GlobalConfig) -> Result<(), Vec<String>> {
+        check_not_empty(&self.services)
+    }
 }
 
 #[async_trait]
 impl Component for UserServiceStatus {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
         println!("User Services:");
-        print_or_error(&self.services, true)
-            .unwrap_or_else(|err| println!("User service status error: {}", err));
-        println!();
+        print_or_error(&self.services, true, global_config)
+            .unwrap_or_else(|err| print_error("User service status error", err));
     }
     default_prepare!();
+
+    fn validate(&self, _global_config: &GlobalConfig) -> Result<(), Vec<String>> {
+        check_not_empty(&self.services)
+    }
 }
 
 #[derive(Error, Debug)]
@@ -45,6 +52,9 @@ pub enum ServiceStatusError {
     #[error("Empty configuration for system services. Please remove the entire block to disable this component.")]
     ConfigEmpty,
 
+    #[error("systemd is not running on this system")]
+    NoSystemd,
+
     #[error(transparent)]
     BetterCommand(#[from] BetterCommandError),
 
@@ -67,13 +77,25 @@ fn get_service_status(service: &str, user: bool) -> Result<String, ServiceStatus
     Ok(output.split_whitespace().collect())
 }
 
+fn check_not_empty(config: &HashMap<String, String>) -> Result<(), Vec<String>> {
+    match config.is_empty() {
+        true => Err(vec![ServiceStatusError::ConfigEmpty.to_string()]),
+        false => Ok(()),
+    }
+}
+
 pub fn print_or_error(
     config: &HashMap<String, String>,
     user: bool,
+    global_config: &GlobalConfig,
 ) -> Result<(), ServiceStatusError> {
     if config.is_empty() {
         return Err(ServiceStatusError::ConfigEmpty);
     }
+    // The same check as sd_booted(3)
+    if !Path::new("/run/systemd/system").exists() {
+        return Err(ServiceStatusError::NoSystemd);
+    }
 
     let padding = config.keys().map(|x| x.len()).max().unwrap();
 
@@ -81,10 +103,10 @@ pub fn print_or_error(
         let status = get_service_status(config.get(key).unwrap(), user)?;
 
         let status_color = match status.as_ref() {
-            "active" => color::Fg(color::Green).to_string(),
-            "inactive" => color::Fg(color::Yellow).to_string(),
-            "failed" => color::Fg(color::Red).to_string(),
-            _ => style::Reset.to_string(),
+            "active" => colors::severity_color(global_config, Severity::Ok),
+            "inactive" => colors::severity_color(global_config, Severity::Warn),
+            "failed" => colors::severity_color(global_config, Severity::Crit),
+            _ => colors::reset(global_config.color),
         };
 
         println!(
@@ -94,7 +116,7 @@ pub fn print_or_error(
             " ".repeat(padding - key.len()),
             status_color,
             status,
-            style::Reset,
+            colors::reset(global_config.color),
         );
     }
 
@@ -1,18 +1,26 @@
 use async_trait::async_trait;
-use chrono::{Duration, TimeZone, Utc};
+use chrono::{DateTime, TimeZone, Utc};
+use openssl::ssl::{HandshakeError, SslConnector, SslMethod, SslVerifyMode};
 use openssl::x509::X509;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Read};
-use termion::{color, style};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
-use crate::component::Component;
+use crate::colors;
+use crate::component::{print_error, Component, Severity};
 use crate::config::global_config::GlobalConfig;
 use crate::constants::INDENT_WIDTH;
 use crate::default_prepare;
 
+/// How much earlier than the component's timeout the certificates are given up on
+const CONNECTION_MARGIN: Duration = Duration::from_millis(100);
+
 #[derive(Debug, Deserialize, Default)]
 enum SortMethod {
     #[serde(alias = "alphabetical")] // Alias used to match lowercase spelling as well
@@ -24,19 +32,45 @@ enum SortMethod {
     Manual,
 }
 
+fn default_warn_days() -> i64 {
+    30
+}
+
+fn default_bad_days() -> i64 {
+    7
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SSLCerts {
     #[serde(default)]
     sort_method: SortMethod,
+    /// Names of the certificates and PEM files or `host:port` of TLS servers to get them from
     certs: HashMap<String, String>,
+    /// Certificates expiring in fewer days than this are shown in yellow
+    #[serde(default = "default_warn_days")]
+    warn_days: i64,
+    /// Certificates expiring in fewer days than this are shown in red
+    #[serde(default = "default_bad_days")]
+    bad_days: i64,
+
+    // Taken from the common `timeout` option, see `with_connection_timeout`
+    #[serde(skip)]
+    timeout: Timeout,
+}
+
+#[derive(Debug)]
+struct Timeout(Duration);
+impl Default for Timeout {
+    fn default() -> Self {
+        Timeout(Duration::from_secs(5))
+    }
 }
 
 #[async_trait]
 impl Component for SSLCerts {
     async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
         self.print_or_error(global_config)
-            .unwrap_or_else(|err| println!("SSL Certificate error: {}", err));
-        println!();
+            .unwrap_or_else(|err| print_error("SSL Certificate error", err));
     }
     default_prepare!();
 }
@@ -51,63 +85,188 @@ pub enum SSLCertsError {
 
     #[error(transparent)]
     ErrorStack(#[from] openssl::error::ErrorStack),
+
+    #[error("Could not resolve {host:?}")]
+    Resolve { host: String },
+
+    #[error("TLS handshake with {host:?} failed: {message}")]
+    Handshake { host: String, message: String },
+
+    #[error("{host:?} did not send a certificate")]
+    NoPeerCertificate { host: String },
+
+    #[error("{cert:?} timed out")]
+    TimedOut { cert: String },
 }
 
 struct CertInfo {
     name: String,
-    status: String,
-    expiration: systemstat::DateTime<systemstat::Utc>,
+    // Certificates that could not be loaded are listed with the error
+    expiration: Result<DateTime<Utc>, SSLCertsError>,
 }
 
 impl SSLCerts {
-    pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), SSLCertsError> {
-        let mut cert_infos: Vec<CertInfo> = Vec::new();
+    /// Use the component's timeout also for loading the certificates
+    pub fn with_connection_timeout(mut self, timeout: Option<Duration>) -> Self {
+        if let Some(timeout) = timeout {
+            // Give up before the runner gives up on the component, so that the errors are shown
+            self.timeout = Timeout(timeout.saturating_sub(CONNECTION_MARGIN));
+        }
+        self
+    }
 
+    pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), SSLCertsError> {
         println!("SSL Certificates:");
-        for (name, path) in self.certs {
-            let cert = File::open(&path)?;
-            let cert = BufReader::new(cert);
-            let cert: Vec<u8> = cert.bytes().collect::<Result<_, _>>()?;
-            let cert = X509::from_pem(&cert)?;
-
-            let expiration =
-                Utc.datetime_from_str(&format!("{}", cert.not_after()), "%B %_d %T %Y %Z")?;
-
-            let now = Utc::now();
-            let status = if expiration < now {
-                format!("{}expired on{}", color::Fg(color::Red), style::Reset)
-            } else if expiration < now + Duration::days(30) {
-                format!("{}expiring on{}", color::Fg(color::Yellow), style::Reset)
-            } else {
-                format!("{}valid until{}", color::Fg(color::Green), style::Reset)
-            };
-            cert_infos.push(CertInfo {
-                name,
-                status,
-                expiration,
-            });
-        }
+        let mut cert_infos = self.load_certs();
 
         match self.sort_method {
             SortMethod::Alphabetical => {
                 cert_infos.sort_by(|a, b| a.name.cmp(&b.name));
             }
             SortMethod::Expiration => {
-                cert_infos.sort_by(|a, b| a.expiration.cmp(&b.expiration));
+                // Certificates with errors come first
+                cert_infos.sort_by_key(|a| a.expiration.as_ref().ok().copied());
             }
             SortMethod::Manual => {}
         }
 
+        let now = Utc::now();
+        let indent = " ".repeat(INDENT_WIDTH);
         for cert_info in cert_infos.into_iter() {
+            let expiration = match cert_info.expiration {
+                Ok(expiration) => expiration,
+                Err(err) => {
+                    print_error(&format!("{indent}{}", cert_info.name), err);
+                    continue;
+                }
+            };
+            let days = (expiration - now).num_days();
+            let (status_color, status, days) = if expiration < now {
+                (
+                    colors::severity_color(global_config, Severity::Crit),
+                    "EXPIRED on",
+                    format!("{} days ago", -days),
+                )
+            } else if days < self.bad_days {
+                (
+                    colors::severity_color(global_config, Severity::Crit),
+                    "expiring on",
+                    format!("{days} days left"),
+                )
+            } else if days < self.warn_days {
+                (
+                    colors::severity_color(global_config, Severity::Warn),
+                    "expiring on",
+                    format!("{days} days left"),
+                )
+            } else {
+                (
+                    colors::severity_color(global_config, Severity::Ok),
+                    "valid until",
+                    format!("{days} days left"),
+                )
+            };
             println!(
-                "{}{} {} {}",
-                " ".repeat(INDENT_WIDTH),
+                "{indent}{} {status_color}{status}{reset} {} ({days})",
                 cert_info.name,
-                cert_info.status,
-                cert_info.expiration.format(&global_config.time_format)
+                expiration.format(&global_config.time_format),
+                reset = colors::reset(global_config.color),
             );
         }
 
         Ok(())
     }
+
+    /// Load all certificates at once, so that each TLS server may take the whole timeout
+    /// Certificates that are not loaded in time are reported as timed out
+    fn load_certs(&self) -> Vec<CertInfo> {
+        let deadline = Instant::now() + self.timeout.0;
+        let (sender, receiver) = mpsc::channel();
+        for (index, source) in self.certs.values().enumerate() {
+            let sender = sender.clone();
+            let source = source.clone();
+            // Not joined, a server that hangs (e.g. in name resolution) is left behind
+            thread::spawn(move || {
+                let expiration = load_cert(&source, deadline).and_then(|cert| {
+                    Ok(Utc.datetime_from_str(&format!("{}", cert.not_after()), "%B %_d %T %Y %Z")?)
+                });
+                let _ = sender.send((index, expiration));
+            });
+        }
+        drop(sender);
+
+        let mut expirations: Vec<_> = self.certs.iter().map(|_| None).collect();
+        while let Ok((index, expiration)) =
+            receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            expirations[index] = Some(expiration);
+        }
+        self.certs
+            .iter()
+            .zip(expirations)
+            .map(|((name, source), expiration)| CertInfo {
+                name: name.clone(),
+                expiration: expiration.unwrap_or_else(|| {
+                    Err(SSLCertsError::TimedOut {
+                        cert: source.clone(),
+                    })
+                }),
+            })
+            .collect()
+    }
+}
+
+/// Read the certificate from a PEM file or, if `source` is of the
+/// form `host:port`, from the TLS server listening there
+fn load_cert(source: &str, deadline: Instant) -> Result<X509, SSLCertsError> {
+    if !source.contains('/') {
+        if let Some((host, port)) = source.rsplit_once(':') {
+            if let Ok(port) = port.parse::<u16>() {
+                return fetch_cert(host, port, deadline);
+            }
+        }
+    }
+    let cert = File::open(source)?;
+    let cert = BufReader::new(cert);
+    let cert: Vec<u8> = cert.bytes().collect::<Result<_, _>>()?;
+    Ok(X509::from_pem(&cert)?)
+}
+
+/// Get the certificate of a TLS server without verifying it,
+/// so that expired certificates are reported rather than rejected
+fn fetch_cert(host: &str, port: u16, deadline: Instant) -> Result<X509, SSLCertsError> {
+    let timed_out = || SSLCertsError::TimedOut {
+        cert: format!("{host}:{port}"),
+    };
+    // Time left until the deadline, the zero timeout is not allowed by the socket functions
+    let remaining = || match deadline.saturating_duration_since(Instant::now()) {
+        Duration::ZERO => Err(timed_out()),
+        remaining => Ok(remaining),
+    };
+    let addr = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| SSLCertsError::Resolve { host: host.into() })?;
+    let stream = TcpStream::connect_timeout(&addr, remaining()?)?;
+    // Do not let a stalled handshake block the whole MOTD
+    stream.set_read_timeout(Some(remaining()?))?;
+    stream.set_write_timeout(Some(remaining()?))?;
+
+    let mut connector = SslConnector::builder(SslMethod::tls())?;
+    connector.set_verify(SslVerifyMode::NONE);
+    let stream = connector
+        .build()
+        .connect(host, stream)
+        .map_err(|err| match err {
+            // This is how the read and write timeouts surface
+            HandshakeError::WouldBlock(_) => timed_out(),
+            err => SSLCertsError::Handshake {
+                host: host.into(),
+                message: err.to_string(),
+            },
+        })?;
+    stream
+        .ssl()
+        .peer_certificate()
+        .ok_or_else(|| SSLCertsError::NoPeerCertificate { host: host.into() })
 }
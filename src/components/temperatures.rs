@@ -0,0 +1,166 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use systemstat::{Platform, System};
+
+use crate::colors;
+use crate::component::{print_error, Component};
+use crate::config::global_config::GlobalConfig;
+use crate::constants::INDENT_WIDTH;
+use crate::default_prepare;
+
+const HWMON_PATH: &str = "/sys/class/hwmon";
+
+fn default_warn_temp() -> f32 {
+    70.0
+}
+
+fn default_bad_temp() -> f32 {
+    85.0
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Temperatures {
+    /// Labels or chip names of the sensors to show, all sensors if empty
+    #[serde(default)]
+    sensors: Vec<String>,
+    /// Temperatures in °C from which the value is printed in `warn_color` and `bad_color`
+    #[serde(default = "default_warn_temp")]
+    warn_temp: f32,
+    #[serde(default = "default_bad_temp")]
+    bad_temp: f32,
+}
+
+#[async_trait]
+impl Component for Temperatures {
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
+        self.print_or_error(global_config)
+            .unwrap_or_else(|err| print_error("Temperatures error", err));
+    }
+    default_prepare!();
+
+    fn validate(&self, _global_config: &GlobalConfig) -> Result<(), Vec<String>> {
+        match self.warn_temp < self.bad_temp {
+            true => Ok(()),
+            false => Err(vec![format!(
+                "warn_temp ({}) must be lower than bad_temp ({})",
+                self.warn_temp, self.bad_temp
+            )]),
+        }
+    }
+}
+
+/// A temperature input of a hardware monitoring chip
+struct Sensor {
+    chip: String,
+    label: String,
+    celsius: f32,
+}
+
+impl Temperatures {
+    pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), std::io::Error> {
+        let mut sensors = read_hwmon(Path::new(HWMON_PATH))?;
+        if sensors.is_empty() {
+            // Some platforms only expose a thermal zone
+            if let Ok(celsius) = System::new().cpu_temp() {
+                sensors.push(Sensor {
+                    chip: "cpu".to_string(),
+                    label: "CPU".to_string(),
+                    celsius,
+                });
+            }
+        }
+        if !self.sensors.is_empty() {
+            sensors.retain(|s| self.sensors.contains(&s.label) || self.sensors.contains(&s.chip));
+        }
+
+        println!("Temperatures:");
+        let indent = " ".repeat(INDENT_WIDTH);
+        if sensors.is_empty() {
+            println!("{indent}no temperature sensors");
+            return Ok(());
+        }
+
+        let label_width = sensors.iter().map(|s| s.label.chars().count() + 1).max();
+        for sensor in sensors {
+            println!(
+                "{indent}{:<width$} {}{:.0}°C{}",
+                format!("{}:", sensor.label),
+                colors::threshold_color(
+                    global_config,
+                    sensor.celsius.into(),
+                    self.warn_temp.into(),
+                    self.bad_temp.into()
+                ),
+                sensor.celsius,
+                colors::reset(global_config.color),
+                width = label_width.unwrap_or(0),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Read all `temp*_input` files of the hardware monitoring chips
+/// Sensors that cannot be read (some chips report errors when asleep) are skipped
+fn read_hwmon(path: &Path) -> Result<Vec<Sensor>, std::io::Error> {
+    let mut chips: Vec<_> = match fs::read_dir(path) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+    chips.sort();
+
+    let mut sensors = Vec::new();
+    for chip_path in chips {
+        let chip = fs::read_to_string(chip_path.join("name"))
+            .map(|name| name.trim().to_string())
+            .unwrap_or_else(|_| {
+                chip_path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            });
+        let Ok(entries) = fs::read_dir(&chip_path) else {
+            continue;
+        };
+        // Indexes of the chip's temperature inputs, e.g. 1 for `temp1_input`
+        let mut indexes: Vec<u32> = entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().into_string().ok()?;
+                name.strip_prefix("temp")?
+                    .strip_suffix("_input")?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        indexes.sort();
+
+        for &index in &indexes {
+            let Some(millidegrees) =
+                fs::read_to_string(chip_path.join(format!("temp{index}_input")))
+                    .ok()
+                    .and_then(|value| value.trim().parse::<i64>().ok())
+            else {
+                continue;
+            };
+            let label = match fs::read_to_string(chip_path.join(format!("temp{index}_label"))) {
+                Ok(label) => label.trim().to_string(),
+                Err(_) if indexes.len() == 1 => chip.clone(),
+                Err(_) => format!("{chip} temp{index}"),
+            };
+            sensors.push(Sensor {
+                chip: chip.clone(),
+                label,
+                celsius: millidegrees as f32 / 1000.0,
+            });
+        }
+    }
+    Ok(sensors)
+}
@@ -0,0 +1,129 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::colors;
+use crate::command::{BetterCommand, BetterCommandError};
+use crate::component::{print_error, Component, Severity};
+use crate::config::global_config::GlobalConfig;
+use crate::default_prepare;
+
+/// Whether the system clock is synchronized by NTP
+#[derive(Debug, Deserialize)]
+pub struct TimeSync {
+    /// Also show the offset of the system clock from the NTP time
+    #[serde(default)]
+    show_offset: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum TimeSyncError {
+    #[error(transparent)]
+    BetterCommand(#[from] BetterCommandError),
+}
+
+#[async_trait]
+impl Component for TimeSync {
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
+        self.print_or_error(global_config)
+            .unwrap_or_else(|err| print_error("Time sync error", err));
+    }
+    default_prepare!();
+}
+
+impl TimeSync {
+    pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), TimeSyncError> {
+        let label = global_config.pad_label("NTP synchronized:");
+        // timedatectl fails if systemd is not running, e.g. in containers
+        let synchronized = match timedatectl_synchronized() {
+            Ok(synchronized) => Some(synchronized),
+            Err(_) => chrony_tracking()?.map(|(synchronized, _)| synchronized),
+        };
+        let Some(synchronized) = synchronized else {
+            println!(
+                "{label} {}unknown{} (neither systemd nor chrony is available)",
+                colors::severity_color(global_config, Severity::Warn),
+                colors::reset(global_config.color)
+            );
+            return Ok(());
+        };
+
+        let (severity, status) = match synchronized {
+            true => (Severity::Ok, "yes"),
+            false => (Severity::Crit, "no"),
+        };
+        let offset = match self.show_offset {
+            true => offset()
+                .map(|o| format!(" (offset {o})"))
+                .unwrap_or_default(),
+            false => "".to_string(),
+        };
+        println!(
+            "{label} {}{status}{}{offset}",
+            colors::severity_color(global_config, severity),
+            colors::reset(global_config.color)
+        );
+
+        Ok(())
+    }
+}
+
+/// The `NTPSynchronized` property of systemd, i.e. the kernel's view regardless of the NTP daemon
+fn timedatectl_synchronized() -> Result<bool, BetterCommandError> {
+    let output = BetterCommand::new("timedatectl")
+        .args(["show", "--property=NTPSynchronized", "--value"])
+        .check_status_and_get_output_string()?;
+    Ok(output.trim() == "yes")
+}
+
+/// Synchronization status and offset (e.g. `+0.012ms`) reported by `chronyc tracking`
+/// None if chrony is not installed
+fn chrony_tracking() -> Result<Option<(bool, Option<String>)>, BetterCommandError> {
+    let output = match BetterCommand::new("chronyc")
+        .arg("tracking")
+        .check_status_and_get_output_string()
+    {
+        Ok(output) => output,
+        Err(BetterCommandError::NotFound { .. }) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let mut synchronized = true;
+    let mut offset = None;
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        match key.trim() {
+            "Leap status" => synchronized = value.trim() != "Not synchronised",
+            // E.g. "0.000012345 seconds fast of NTP time"
+            "System time" => {
+                let mut words = value.split_whitespace();
+                if let (Some(Ok(secs)), _, Some(direction)) = (
+                    words.next().map(str::parse::<f64>),
+                    words.next(),
+                    words.next(),
+                ) {
+                    let sign = if direction == "slow" { -1.0 } else { 1.0 };
+                    offset = Some(format!("{:+.3}ms", sign * secs * 1000.0));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(Some((synchronized, offset)))
+}
+
+/// Offset of the system clock from chrony or, failing that, from systemd-timesyncd
+fn offset() -> Option<String> {
+    if let Ok(Some((_, offset))) = chrony_tracking() {
+        return offset;
+    }
+    let output = BetterCommand::new("timedatectl")
+        .arg("timesync-status")
+        .check_status_and_get_output_string()
+        .ok()?;
+    output.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == "Offset").then(|| value.trim().to_string())
+    })
+}
@@ -1,33 +1,63 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
+use chrono::Local;
 use humantime::format_duration;
 use serde::Deserialize;
 use systemstat::{Platform, System};
 
-use crate::component::Component;
+use crate::component::{print_error, Component};
 use crate::config::global_config::GlobalConfig;
 use crate::default_prepare;
+use crate::rounding::{round_duration, Granularity};
+
+const DAY: u64 = 24 * 60 * 60;
 
 #[derive(Debug, Deserialize)]
 pub struct Uptime {
     prefix: String,
+    /// Also print when the system was booted, formatted with `time_format`
+    #[serde(default)]
+    show_boot_time: bool,
+    /// Round the uptime to this unit (default: minutes, or hours once it exceeds a day)
+    duration_granularity: Option<Granularity>,
 }
 
 #[async_trait]
 impl Component for Uptime {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error()
-            .unwrap_or_else(|err| println!("Uptime error: {}", err));
-        println!();
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
+        self.print_or_error(global_config)
+            .unwrap_or_else(|err| print_error("Uptime error", err));
     }
     default_prepare!();
 }
 
 impl Uptime {
-    pub fn print_or_error(self) -> Result<(), std::io::Error> {
+    pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), std::io::Error> {
         let sys = System::new();
         let uptime = sys.uptime()?;
-        println!("{} {}", self.prefix, format_duration(uptime));
+        print!(
+            "{} {}",
+            global_config.pad_label(&self.prefix),
+            format_duration(self.round_uptime(uptime))
+        );
+        if self.show_boot_time {
+            // Uptime is far below the range where the conversion could fail
+            let boot_time = Local::now() - chrono::Duration::from_std(uptime).unwrap();
+            print!(" (since {})", boot_time.format(&global_config.time_format));
+        }
+        println!();
 
         Ok(())
     }
+
+    /// Round the uptime to whole minutes, or to hours once it exceeds a day,
+    /// so that the output does not end in a meaningless number of seconds
+    fn round_uptime(&self, uptime: Duration) -> Duration {
+        let granularity = self.duration_granularity.unwrap_or(match uptime.as_secs() {
+            s if s < DAY => Granularity::Minutes,
+            _ => Granularity::Hours,
+        });
+        round_duration(uptime, granularity)
+    }
 }
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use chrono::{Local, TimeZone};
+use humantime::format_duration;
+use serde::Deserialize;
+use thiserror::Error;
+use utmp_rs::{ParseError, UtmpEntry};
+
+use crate::component::{print_error, Component};
+use crate::config::global_config::GlobalConfig;
+use crate::constants::INDENT_WIDTH;
+use crate::default_prepare;
+use crate::rounding::{round_duration, Granularity};
+
+const UTMP_PATH: &str = "/var/run/utmp";
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Users {
+    /// Show the terminals the users are logged in on
+    #[serde(default = "default_true")]
+    show_tty: bool,
+    /// Show how long the terminals have been idle
+    #[serde(default)]
+    show_idle: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum UsersError {
+    #[error("Cannot read {UTMP_PATH}: {0}")]
+    Utmp(#[from] ParseError),
+}
+
+#[async_trait]
+impl Component for Users {
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
+        self.print_or_error(global_config)
+            .unwrap_or_else(|err| print_error("Users error", err));
+    }
+    default_prepare!();
+}
+
+/// A login session of a user
+struct Session {
+    tty: String,
+    login_time: i64,        // Unix timestamp
+    idle: Option<Duration>, // None if the terminal cannot be examined
+}
+
+impl Users {
+    pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), UsersError> {
+        // Users with all their sessions, sorted by name
+        let mut users: BTreeMap<String, Vec<Session>> = BTreeMap::new();
+        for entry in utmp_rs::parse_from_path(UTMP_PATH)? {
+            if let UtmpEntry::UserProcess {
+                line, user, time, ..
+            } = entry
+            {
+                users.entry(user).or_default().push(Session {
+                    idle: idle_time(&line),
+                    tty: line,
+                    login_time: time.unix_timestamp(),
+                });
+            }
+        }
+
+        println!("Logged in users:");
+        if users.is_empty() {
+            println!("{}no users logged in", " ".repeat(INDENT_WIDTH));
+            return Ok(());
+        }
+
+        let name_width = users.keys().map(|name| name.chars().count()).max();
+        for (name, mut sessions) in users {
+            sessions.sort_by_key(|s| s.login_time);
+            // Many sessions of one user are summarized in a single line
+            let first_login = sessions[0].login_time;
+            let mut line = format!(
+                "{}{:<width$}",
+                " ".repeat(INDENT_WIDTH),
+                name,
+                width = name_width.unwrap_or(0)
+            );
+            if sessions.len() > 1 {
+                line += &format!(" {} sessions", sessions.len());
+                if self.show_tty {
+                    line += " on";
+                }
+            }
+            if self.show_tty {
+                let ttys: Vec<_> = sessions.iter().map(|s| s.tty.as_str()).collect();
+                line += &format!(" {}", ttys.join(", "));
+            }
+            if let Some(login_time) = Local.timestamp_opt(first_login, 0).single() {
+                line += &format!(
+                    " {} {}",
+                    if sessions.len() > 1 { "since" } else { "at" },
+                    login_time.format(&global_config.time_format)
+                );
+            }
+            if self.show_idle {
+                // The user is as idle as their most recently used terminal
+                if let Some(idle) = sessions.iter().filter_map(|s| s.idle).min() {
+                    line += &format!(
+                        " (idle {})",
+                        format_duration(round_duration(idle, Granularity::Minutes))
+                    );
+                }
+            }
+            println!("{line}");
+        }
+
+        Ok(())
+    }
+}
+
+/// How long ago the terminal was last used, as `who -u` computes it
+fn idle_time(tty: &str) -> Option<Duration> {
+    let accessed = fs::metadata(format!("/dev/{tty}")).ok()?.accessed().ok()?;
+    SystemTime::now().duration_since(accessed).ok()
+}
@@ -1,13 +1,21 @@
 use async_trait::async_trait;
 use serde::Deserialize;
 use std::io::Write;
+use std::time::Duration;
 use thiserror::Error;
 use ureq;
-use std::time::Duration;
 
-use crate::component::Component;
+use crate::cache::{self, CachedOutput};
+use crate::colors;
+use crate::component::{print_error, Component, PrepareReturn};
 use crate::config::global_config::GlobalConfig;
 use crate::default_prepare;
+use crate::rounding::{round_duration, Granularity};
+
+/// Name under which the last successfully fetched weather of the first section is cached
+const LAST_WEATHER: &str = "weather-last";
+/// How much earlier than the component's timeout the HTTP request gives up
+const REQUEST_MARGIN: Duration = Duration::from_millis(100);
 
 #[derive(Debug, Deserialize)]
 pub struct Weather {
@@ -15,33 +23,105 @@ pub struct Weather {
     user_agent: Option<String>,
     proxy: Option<String>,
 
-    #[serde(default = "String::new")]
+    #[serde(default = "String::new", alias = "location")]
     loc: String,
 
     style: Option<WeatherStyle>,
 
-    #[serde(default)]
+    /// Units of the temperature and wind speed (default: chosen by the service)
+    units: Option<Units>,
+
+    /// If set, the weather is fetched from OpenWeatherMap instead of wttr.in
+    api_key: Option<String>,
+
+    // Taken from the common `timeout` option, see `with_request_timeout`
+    #[serde(skip)]
     timeout: Timeout,
+
+    // Position among the `[[weather]]` sections, see `with_section_number`
+    #[serde(skip)]
+    section_number: usize,
 }
 
-#[derive(Deserialize, Debug)]
-struct Timeout(u64);
+#[derive(Debug)]
+struct Timeout(Duration);
 impl Default for Timeout {
     fn default() -> Self {
-        Timeout(5)
+        Timeout(Duration::from_secs(5))
     }
 }
 
 #[async_trait]
 impl Component for Weather {
+    // The runner prints the `PreparedWeather` returned by `prepare`, so this only
+    // serves callers that print without preparing first
+    async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>) {
+        self.prepare(global_config)
+            .0
+            .print(global_config, width)
+            .await
+    }
+
+    /// Fetch the weather already in the prepare phase, which runs concurrently
+    /// with the other components
+    fn prepare(self: Box<Self>, global_config: &GlobalConfig) -> PrepareReturn {
+        let weather = self.fetch(global_config);
+        if let Ok(output) = &weather {
+            if !global_config.dry_run {
+                let cached = CachedOutput {
+                    min_width: None,
+                    output: output.clone(),
+                };
+                if let Err(e) = cache::write(&self.cache_name(), &cached) {
+                    eprintln!("Cannot cache the weather: {e}");
+                }
+            }
+        }
+        let cache_name = self.cache_name();
+        (
+            Box::new(PreparedWeather {
+                weather,
+                cache_name,
+            }),
+            None,
+        )
+    }
+}
+
+/// Fetched weather ready to be printed
+pub struct PreparedWeather {
+    weather: Result<String, WeatherError>,
+    cache_name: String,
+}
+
+#[async_trait]
+impl Component for PreparedWeather {
     async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error()
-            .unwrap_or_else(|err| println!("Weather error: {}", err));
-        println!();
+        match self.weather {
+            Ok(output) => print!("{output}"),
+            // Show the last known weather rather than nothing if the service is unreachable
+            Err(err) => match cache::read_with_age(&self.cache_name) {
+                Some((cached, age)) => {
+                    print!("{}", cached.output);
+                    println!(
+                        "(as of {} ago, update failed: {err})",
+                        humantime::format_duration(round_duration(age, Granularity::Minutes))
+                    );
+                }
+                None => print_error("Weather error", err),
+            },
+        }
     }
     default_prepare!();
 }
 
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Units {
+    Metric,
+    Imperial,
+}
+
 #[derive(Debug, Deserialize)]
 enum WeatherStyle {
     #[serde(alias = "oneline")]
@@ -58,6 +138,9 @@ pub enum WeatherError {
     #[error("Empty response body from weather service")]
     ReplyEmpty,
 
+    #[error("Unexpected response from OpenWeatherMap: {0}")]
+    OpenWeatherMap(#[from] serde_json::Error),
+
     #[error(transparent)]
     Ureq(#[from] ureq::Error),
 
@@ -66,41 +149,67 @@ pub enum WeatherError {
 }
 
 impl Weather {
-    pub fn print_or_error(self) -> Result<(), WeatherError> {
-        let url = match self.url {
-            Some(url) => url,
+    /// Use the component's timeout also for the HTTP request
+    pub fn with_request_timeout(mut self, timeout: Option<Duration>) -> Self {
+        if let Some(timeout) = timeout {
+            // Give up before the runner gives up on the component, so that the error is shown
+            self.timeout = Timeout(timeout.saturating_sub(REQUEST_MARGIN));
+        }
+        self
+    }
+
+    /// Number the sections of `[[weather]]` from 1, so that each caches its own weather
+    pub fn with_section_number(mut self, section_number: usize) -> Self {
+        self.section_number = section_number;
+        self
+    }
+
+    /// Name under which the last successfully fetched weather of this section is cached
+    fn cache_name(&self) -> String {
+        match self.section_number {
+            0 | 1 => LAST_WEATHER.into(),
+            n => format!("{LAST_WEATHER}#{n}"),
+        }
+    }
+
+    /// Fetch the weather and render it as the text to print
+    #[allow(clippy::result_large_err)]
+    fn fetch(&self, global_config: &GlobalConfig) -> Result<String, WeatherError> {
+        if let Some(api_key) = &self.api_key {
+            return self.fetch_openweathermap(api_key);
+        }
+
+        let url = match &self.url {
+            Some(url) => url.clone(),
             None => {
                 let mut base = String::from("https://wttr.in/");
                 let loc = self.loc.replace(", ", ",").replace(' ', "+");
                 base.push_str(&loc);
+                let mut query = vec![];
                 match &self.style.as_ref().unwrap_or(&WeatherStyle::Day) {
-                    WeatherStyle::Oneline => base.push_str("?format=4"),
-                    WeatherStyle::Day => base.push_str("?0"),
+                    WeatherStyle::Oneline => query.push("format=4"),
+                    WeatherStyle::Day => query.push("0"),
                     WeatherStyle::Full => (),
                 }
+                match self.units {
+                    Some(Units::Metric) => query.push("m"),
+                    Some(Units::Imperial) => query.push("u"),
+                    None => (),
+                }
+                if !query.is_empty() {
+                    base.push('?');
+                    base.push_str(&query.join("&"));
+                }
                 base
             }
         };
 
-        let mut agent = ureq::AgentBuilder::new().
-            timeout(Duration::from_secs(self.timeout.0));
-        if let Some(proxy) = self.proxy {
-            let proxy = ureq::Proxy::new(proxy)?;
-            agent = agent.proxy(proxy);
-        }
-        let agent = agent.build();
-
-        let user_agent = match self.user_agent {
-            Some(user_agent) => user_agent,
-            None => String::from("curl"),
+        let body = self.get(&url, &[])?;
+        let body = match global_config.color {
+            true => body,
+            false => colors::strip_escapes(&body).into_owned(),
         };
 
-        let body = agent
-            .get(&url)
-            .set("User-Agent", &user_agent)
-            .call()?
-            .into_string()?;
-
         let mut body = body.lines();
         let first_line = body
             .next()
@@ -114,11 +223,72 @@ impl Weather {
             .collect::<Vec<String>>()
             .join("");
 
-        let mut out = std::io::stdout();
+        let mut out = Vec::new();
         out.write_all(&[first_line.as_bytes(), "\n".as_bytes()].concat())?;
         out.write_all(body.as_bytes())?;
-        out.flush()?;
 
-        Ok(())
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+
+    /// One line summary of the current weather from OpenWeatherMap
+    #[allow(clippy::result_large_err)]
+    fn fetch_openweathermap(&self, api_key: &str) -> Result<String, WeatherError> {
+        #[derive(Deserialize)]
+        struct Reply {
+            name: String,
+            weather: Vec<Condition>,
+            main: Main,
+        }
+        #[derive(Deserialize)]
+        struct Condition {
+            description: String,
+        }
+        #[derive(Deserialize)]
+        struct Main {
+            temp: f64,
+        }
+
+        let (units, symbol) = match self.units.unwrap_or(Units::Metric) {
+            Units::Metric => ("metric", "°C"),
+            Units::Imperial => ("imperial", "°F"),
+        };
+        let query = [
+            ("q", self.loc.as_str()),
+            ("appid", api_key),
+            ("units", units),
+        ];
+        let reply: Reply = serde_json::from_str(
+            &self.get("https://api.openweathermap.org/data/2.5/weather", &query)?,
+        )?;
+        let conditions: Vec<_> = reply.weather.into_iter().map(|c| c.description).collect();
+        Ok(format!(
+            "{}: {:.0}{symbol}, {}\n",
+            reply.name,
+            reply.main.temp,
+            conditions.join(", ")
+        ))
+    }
+
+    /// Body of the response to a GET request of `url` with the `query` parameters,
+    /// which are URL-encoded
+    #[allow(clippy::result_large_err)]
+    fn get(&self, url: &str, query: &[(&str, &str)]) -> Result<String, WeatherError> {
+        let mut agent = ureq::AgentBuilder::new().timeout(self.timeout.0);
+        if let Some(proxy) = &self.proxy {
+            let proxy = ureq::Proxy::new(proxy)?;
+            agent = agent.proxy(proxy);
+        }
+        let agent = agent.build();
+
+        let user_agent = match &self.user_agent {
+            Some(user_agent) => user_agent.as_str(),
+            None => "curl",
+        };
+
+        let mut request = agent.get(url).set("User-Agent", user_agent);
+        for (param, value) in query {
+            request = request.query(param, value);
+        }
+        Ok(request.call()?.into_string()?)
     }
 }
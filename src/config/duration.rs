@@ -0,0 +1,37 @@
+use serde::de::{self, Deserializer, Visitor};
+use std::fmt;
+use std::time::Duration;
+
+/// Deserialize an optional human readable duration such as "30s" or "5min"
+/// Plain integers are interpreted as a number of seconds
+/// Use together with `#[serde(default)]` so that a missing field is `None`
+pub fn deserialize_optional<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct DurationVisitor;
+
+    impl<'de> Visitor<'de> for DurationVisitor {
+        type Value = Duration;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a duration such as \"30s\" or a number of seconds")
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Duration, E> {
+            humantime::parse_duration(value).map_err(E::custom)
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<Duration, E> {
+            Ok(Duration::from_secs(value))
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<Duration, E> {
+            u64::try_from(value)
+                .map(Duration::from_secs)
+                .map_err(|_| E::custom(format!("duration must not be negative, got {value}")))
+        }
+    }
+
+    deserializer.deserialize_any(DurationVisitor).map(Some)
+}
@@ -2,6 +2,7 @@ use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
+use toml::value::{Table, Value};
 
 use crate::config::Config;
 
@@ -22,6 +23,18 @@ pub enum ConfigError {
 
     #[error(transparent)]
     ConfigParseError(#[from] toml::de::Error),
+
+    #[error("{}: {source}", path.display())]
+    Include {
+        path: PathBuf,
+        source: Box<ConfigError>,
+    },
+
+    #[error("{} is included by itself, directly or through other files", .0.display())]
+    IncludeCycle(PathBuf),
+
+    #[error("Invalid configuration:\n  {}", .0.join("\n  "))]
+    InvalidConfig(Vec<String>),
 }
 
 pub fn get_config(config_path: Option<String>) -> Result<Config, ConfigError> {
@@ -38,7 +51,80 @@ pub fn get_config(config_path: Option<String>) -> Result<Config, ConfigError> {
         }
     };
     match config_path {
-        Some(path) => Ok(toml::from_str(&fs::read_to_string(path)?)?),
+        Some(path) => {
+            let mut config: Config = Value::Table(read_table(&path, &mut vec![])?).try_into()?;
+            config.global.finalize();
+            config.validate().map_err(ConfigError::InvalidConfig)?;
+            // Honor https://no-color.org/
+            if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+                config.global.color = false;
+            }
+            Ok(config)
+        }
         None => Err(ConfigError::ConfigNotFound),
     }
 }
+
+/// Read a configuration file merged with the files it includes
+/// `including` are the files whose includes are being read, to detect cycles
+fn read_table(path: &Path, including: &mut Vec<PathBuf>) -> Result<Table, ConfigError> {
+    let mut table: Table = toml::from_str(&fs::read_to_string(path)?)?;
+    let includes: Vec<PathBuf> = match table.remove("include") {
+        Some(include) => include.try_into()?,
+        None => return Ok(table),
+    };
+    let canonical = path.canonicalize()?;
+    if including.contains(&canonical) {
+        return Err(ConfigError::IncludeCycle(path.to_owned()));
+    }
+    including.push(canonical);
+    // Included files come first, so that the including file overrides them
+    let mut merged = Table::new();
+    for include in includes {
+        // Relative to the directory of the including file
+        let include = path.parent().unwrap_or(Path::new("")).join(include);
+        let included = read_table(&include, including).map_err(|err| match err {
+            // Already names the file
+            ConfigError::IncludeCycle(_) => err,
+            err => ConfigError::Include {
+                path: include,
+                source: Box::new(err),
+            },
+        })?;
+        merge_sections(&mut merged, included);
+    }
+    including.pop();
+    merge_sections(&mut merged, table);
+    Ok(merged)
+}
+
+/// Merge the sections of a configuration file into those read before
+/// Keys of sections given as tables in both are overridden, otherwise the
+/// sections are concatenated as if they were given as an array of tables
+fn merge_sections(merged: &mut Table, sections: Table) {
+    for (name, section) in sections {
+        let Some(existing) = merged.get_mut(&name) else {
+            merged.insert(name, section);
+            continue;
+        };
+        *existing = match (std::mem::replace(existing, Value::Array(vec![])), section) {
+            (Value::Table(mut existing), Value::Table(section)) => {
+                existing.extend(section);
+                Value::Table(existing)
+            }
+            (existing, section) => {
+                let mut list = into_list(existing);
+                list.extend(into_list(section));
+                Value::Array(list)
+            }
+        };
+    }
+}
+
+/// Sections of a component given either as a table or an array of tables
+fn into_list(section: Value) -> Vec<Value> {
+    match section {
+        Value::Array(list) => list,
+        section => vec![section],
+    }
+}
@@ -1,19 +1,130 @@
+use clap::ValueEnum;
 use serde::Deserialize;
+use std::time::Duration;
 
-#[derive(Debug, Deserialize)]
+use crate::colors::{ColorName, ConfigColor, HexColor};
+use crate::component::validation_result;
+use crate::config::duration;
+use crate::constants::INDENT_WIDTH;
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct GlobalConfig {
-    #[serde(default = "default_progress_character")]
+    /// The following four fields are set by `finalize` from `progress_characters`
+    /// and `progress_style`
+    #[serde(skip, default = "default_progress_character")]
     pub progress_full_character: char,
-    #[serde(default = "default_progress_character")]
+    #[serde(skip, default = "default_progress_character")]
     pub progress_empty_character: char,
-    #[serde(default = "default_progress_prefix")]
+    #[serde(skip, default = "default_progress_prefix")]
     pub progress_prefix: String,
-    #[serde(default = "default_progress_suffix")]
+    #[serde(skip, default = "default_progress_suffix")]
     pub progress_suffix: String,
+    progress_style: Option<ProgressStyle>,
+    #[serde(flatten)]
+    progress_characters: ProgressCharacters,
     #[serde(default = "default_progress_width")]
     pub progress_width: usize,
+    #[serde(default)]
+    pub smooth: bool,
+    #[serde(default)]
+    pub bar_value_position: BarValuePosition,
+    #[serde(default = "default_progress_warn_threshold")]
+    pub progress_warn_threshold: f64,
+    #[serde(default = "default_progress_bad_threshold")]
+    pub progress_bad_threshold: f64,
+    pub progress_full_colors: Option<Vec<HexColor>>,
+    #[serde(default = "default_progress_empty_color")]
+    pub progress_empty_color: ConfigColor,
+    #[serde(default = "default_ok_color")]
+    pub ok_color: ConfigColor,
+    #[serde(default = "default_warn_color")]
+    pub warn_color: ConfigColor,
+    #[serde(default = "default_bad_color")]
+    pub bad_color: ConfigColor,
     #[serde(default = "default_time_format")]
     pub time_format: String,
+    #[serde(default = "default_color")]
+    pub color: bool,
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    #[serde(default, deserialize_with = "duration::deserialize_optional")]
+    pub default_timeout: Option<Duration>,
+    #[serde(default, deserialize_with = "duration::deserialize_optional")]
+    pub stale_after: Option<Duration>,
+    #[serde(default)]
+    pub error_exit_code: bool,
+    #[serde(default)]
+    pub severity_exit_code: bool,
+    #[serde(default)]
+    pub show_error_summary: bool,
+    #[serde(default)]
+    pub incremental_output: bool,
+    #[serde(default)]
+    pub debug_layout: bool,
+    #[serde(default)]
+    pub dry_run: bool,
+    pub label_width: Option<usize>,
+    pub indent_width: Option<usize>,
+    pub max_bar_width: Option<usize>,
+    pub component_separator: Option<String>,
+}
+
+/// Named sets of progress bar characters
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum ProgressStyle {
+    Ascii,
+    Blocks,
+    Braille,
+    Arrows,
+}
+
+impl ProgressStyle {
+    /// Full character, empty character, prefix and suffix of the style
+    fn characters(self) -> (char, char, &'static str, &'static str) {
+        match self {
+            ProgressStyle::Ascii => ('#', '-', "[", "]"),
+            ProgressStyle::Blocks => ('█', '░', "", ""),
+            ProgressStyle::Braille => ('⣿', '⣀', "", ""),
+            ProgressStyle::Arrows => ('▸', '▹', "", ""),
+        }
+    }
+}
+
+/// Where the numeric value is printed relative to a progress bar
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BarValuePosition {
+    #[default]
+    Before,
+    After,
+    /// Centered over the bar characters
+    Inside,
+    /// Only the bar is printed
+    None,
+}
+
+/// Progress bar characters given individually in the configuration file
+/// They take precedence over `progress_style`
+#[derive(Debug, Default, Clone, Deserialize)]
+struct ProgressCharacters {
+    progress_full_character: Option<char>,
+    progress_empty_character: Option<char>,
+    progress_prefix: Option<String>,
+    progress_suffix: Option<String>,
+}
+
+/// How the whole MOTD is rendered
+#[derive(Debug, Deserialize, Default, Clone, Copy, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Human readable text for the terminal
+    #[default]
+    Text,
+    /// One JSON object with the data of each component
+    Json,
+    /// Markdown document with one section per component
+    Markdown,
 }
 
 fn default_progress_character() -> char {
@@ -32,10 +143,112 @@ fn default_progress_width() -> usize {
     80
 }
 
+fn default_progress_warn_threshold() -> f64 {
+    0.76
+}
+
+fn default_progress_bad_threshold() -> f64 {
+    0.96
+}
+
+fn default_progress_empty_color() -> ConfigColor {
+    ConfigColor::Named(ColorName::LightBlack)
+}
+
+fn default_ok_color() -> ConfigColor {
+    ConfigColor::Named(ColorName::Green)
+}
+
+fn default_warn_color() -> ConfigColor {
+    ConfigColor::Named(ColorName::Yellow)
+}
+
+fn default_bad_color() -> ConfigColor {
+    ConfigColor::Named(ColorName::Red)
+}
+
 fn default_time_format() -> String {
     "%Y-%m-%d %H:%M:%S".to_string()
 }
 
+fn default_color() -> bool {
+    true
+}
+
+impl GlobalConfig {
+    /// Pad the leading label of a line to `label_width`, so that labels of different components align
+    pub fn pad_label(&self, label: &str) -> String {
+        format!("{:<width$}", label, width = self.label_width.unwrap_or(0))
+    }
+
+    /// Width of one level of indentation of nested lines
+    pub fn indent_width(&self) -> usize {
+        self.indent_width.unwrap_or(INDENT_WIDTH)
+    }
+
+    /// Width of a progress bar that could take `width` characters, limited to `max_bar_width`
+    pub fn bar_width(&self, width: usize) -> usize {
+        self.max_bar_width.map_or(width, |max| width.min(max))
+    }
+
+    /// Copy of the configuration with `color` overridden by a component's own `color` option
+    pub fn with_color(&self, color: bool) -> GlobalConfig {
+        GlobalConfig {
+            color,
+            ..self.clone()
+        }
+    }
+
+    /// Print the separator between two components, an empty line by default
+    /// An empty `component_separator` means no separation at all
+    pub fn print_separator(&self) {
+        match self.component_separator.as_deref() {
+            None => println!(),
+            Some("") => {}
+            Some(separator) => println!("{separator}"),
+        }
+    }
+
+    /// Resolve the progress bar characters from `progress_style` and the individual settings
+    pub fn finalize(&mut self) {
+        let (full, empty, prefix, suffix) = match self.progress_style {
+            Some(style) => {
+                let (full, empty, prefix, suffix) = style.characters();
+                (full, empty, prefix.to_string(), suffix.to_string())
+            }
+            None => (
+                default_progress_character(),
+                default_progress_character(),
+                default_progress_prefix(),
+                default_progress_suffix(),
+            ),
+        };
+        let characters = std::mem::take(&mut self.progress_characters);
+        self.progress_full_character = characters.progress_full_character.unwrap_or(full);
+        self.progress_empty_character = characters.progress_empty_character.unwrap_or(empty);
+        self.progress_prefix = characters.progress_prefix.unwrap_or(prefix);
+        self.progress_suffix = characters.progress_suffix.unwrap_or(suffix);
+    }
+
+    /// Check the values that cannot be checked during deserialization
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+        if self.progress_warn_threshold >= self.progress_bad_threshold {
+            problems.push(format!(
+                "progress_warn_threshold ({}) must be lower than progress_bad_threshold ({})",
+                self.progress_warn_threshold, self.progress_bad_threshold
+            ));
+        }
+        if self.indent_width == Some(0) {
+            problems.push("indent_width must be greater than 0".to_string());
+        }
+        if self.max_bar_width == Some(0) {
+            problems.push("max_bar_width must be greater than 0".to_string());
+        }
+        validation_result(problems)
+    }
+}
+
 // TODO: See if we can use this: https://github.com/serde-rs/serde/issues/1416
 impl Default for GlobalConfig {
     fn default() -> Self {
@@ -44,8 +257,33 @@ impl Default for GlobalConfig {
             progress_empty_character: default_progress_character(),
             progress_prefix: default_progress_prefix(),
             progress_suffix: default_progress_suffix(),
+            progress_style: None,
+            progress_characters: ProgressCharacters::default(),
             progress_width: default_progress_width(),
+            smooth: false,
+            bar_value_position: BarValuePosition::default(),
+            progress_warn_threshold: default_progress_warn_threshold(),
+            progress_bad_threshold: default_progress_bad_threshold(),
+            progress_full_colors: None,
+            progress_empty_color: default_progress_empty_color(),
+            ok_color: default_ok_color(),
+            warn_color: default_warn_color(),
+            bad_color: default_bad_color(),
             time_format: default_time_format(),
+            color: default_color(),
+            output_format: OutputFormat::default(),
+            default_timeout: None,
+            stale_after: None,
+            error_exit_code: false,
+            severity_exit_code: false,
+            show_error_summary: false,
+            incremental_output: false,
+            debug_layout: false,
+            dry_run: false,
+            label_width: None,
+            indent_width: None,
+            max_bar_width: None,
+            component_separator: None,
         }
     }
 }
@@ -1,21 +1,33 @@
+use serde::de::value::MapAccessDeserializer;
 use serde::de::{Deserialize, Visitor};
+use std::convert::identity;
+use std::time::Duration;
 
+pub mod duration;
 pub mod get_config;
 pub mod global_config;
+pub mod ratio;
 
-use crate::component::BoxedComponent;
+use crate::colors::ColorName;
+use crate::component::{validation_result, BoxedComponent, Component};
 use crate::components::banner::Banner;
 use crate::components::cg_stats::CgStats;
+use crate::components::command::Command;
 use crate::components::docker::Docker;
 use crate::components::fail_2_ban::Fail2Ban;
 use crate::components::filesystem::Filesystems;
+use crate::components::host_info::HostInfo;
 use crate::components::last_login::LastLogin;
 use crate::components::last_run::LastRun;
 use crate::components::loadavg::LoadAvg;
 use crate::components::memory::Memory;
+use crate::components::processes::Processes;
 use crate::components::service_status::{ServiceStatus, UserServiceStatus};
 use crate::components::ssl_certs::SSLCerts;
+use crate::components::temperatures::Temperatures;
+use crate::components::time_sync::TimeSync;
 use crate::components::uptime::Uptime;
+use crate::components::users::Users;
 use crate::components::weather::Weather;
 use global_config::GlobalConfig;
 
@@ -27,31 +39,238 @@ enum Fields {
     Global,
     Banner,
     CgStats,
+    Command,
     Docker,
     #[serde(rename = "fail_2_ban")]
     Fail2Ban,
     Filesystems,
+    HostInfo,
     LastLogin,
     LastRun,
     LoadAvg,
     Memory,
+    Processes,
     ServiceStatus,
     UserServiceStatus,
     #[serde(rename = "ssl_certificates")]
     SSLCerts,
+    Temperatures,
+    TimeSync,
     Uptime,
+    Users,
     Weather,
 }
 
+impl Fields {
+    /// Name of the section in the configuration file
+    fn name(&self) -> &'static str {
+        match self {
+            Fields::Global => "global",
+            Fields::Banner => "banner",
+            Fields::CgStats => "cg_stats",
+            Fields::Command => "command",
+            Fields::Docker => "docker",
+            Fields::Fail2Ban => "fail_2_ban",
+            Fields::Filesystems => "filesystems",
+            Fields::HostInfo => "host_info",
+            Fields::LastLogin => "last_login",
+            Fields::LastRun => "last_run",
+            Fields::LoadAvg => "load_avg",
+            Fields::Memory => "memory",
+            Fields::Processes => "processes",
+            Fields::ServiceStatus => "service_status",
+            Fields::UserServiceStatus => "user_service_status",
+            Fields::SSLCerts => "ssl_certificates",
+            Fields::Temperatures => "temperatures",
+            Fields::TimeSync => "time_sync",
+            Fields::Uptime => "uptime",
+            Fields::Users => "users",
+            Fields::Weather => "weather",
+        }
+    }
+}
+
+/// Options that can be given in the section of any component
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct CommonOptions {
+    /// Maximum time the component may take to prepare and to print
+    /// Overrides `default_timeout` from the global configuration
+    #[serde(default, deserialize_with = "duration::deserialize_optional")]
+    pub timeout: Option<Duration>,
+    /// Reuse the output of a previous run if it is younger than this
+    #[serde(default, deserialize_with = "duration::deserialize_optional")]
+    pub cache_ttl: Option<Duration>,
+    /// Position of the component in the output, see `Config::components`
+    pub order: Option<i32>,
+    /// Enable or disable colors of this component, overriding `color` of the global configuration
+    /// For the banner, the color of its text
+    pub color: Option<ColorOption>,
+    /// Print the component on one line with the neighbouring inline components
+    #[serde(default)]
+    pub inline: bool,
+    /// Whether to print the component at all (default true)
+    pub enabled: Option<bool>,
+}
+
+/// The `color` option of a component, which the banner uses for the color of its text
+#[derive(Debug, Clone, Copy)]
+pub enum ColorOption {
+    Enabled(bool),
+    Named(ColorName),
+}
+
+impl<'de> Deserialize<'de> for ColorOption {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct ColorOptionVisitor;
+
+        impl Visitor<'_> for ColorOptionVisitor {
+            type Value = ColorOption;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("true, false or a color name")
+            }
+
+            fn visit_bool<E: serde::de::Error>(self, enabled: bool) -> Result<Self::Value, E> {
+                Ok(ColorOption::Enabled(enabled))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, name: &str) -> Result<Self::Value, E> {
+                name.parse().map(ColorOption::Named).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(ColorOptionVisitor)
+    }
+}
+
+/// A section of the configuration file: the common options plus the component's own options
+/// The common options are taken first so that they are not seen by components
+/// that are configured by a plain map, such as docker or filesystems
+#[derive(serde::Deserialize)]
+struct Section<T> {
+    #[serde(flatten)]
+    common: CommonOptions,
+    #[serde(flatten)]
+    options: T,
+}
+
+impl<T> Section<T> {
+    fn into_entry<C>(self, make_component: impl FnOnce(T) -> C) -> (CommonOptions, BoxedComponent)
+    where
+        C: Component + Send + 'static,
+    {
+        (self.common, Box::new(make_component(self.options)))
+    }
+}
+
+/// One or more sections of the same component, given either as a table (`[cg_stats]`)
+/// or as an array of tables (`[[cg_stats]]`) to configure the component several times
+struct Sections<T>(Vec<Section<T>>);
+
+impl<T> Sections<T> {
+    fn into_entries(
+        self,
+        into_entry: impl FnMut(Section<T>) -> (CommonOptions, BoxedComponent),
+    ) -> Vec<(CommonOptions, BoxedComponent)> {
+        self.0.into_iter().map(into_entry).collect()
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Sections<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct SectionsVisitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for SectionsVisitor<T> {
+            type Value = Sections<T>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a table or an array of tables")
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                Ok(Sections(vec![Section::deserialize(
+                    MapAccessDeserializer::new(map),
+                )?]))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut sections = vec![];
+                while let Some(section) = seq.next_element()? {
+                    sections.push(section);
+                }
+                Ok(Sections(sections))
+            }
+        }
+
+        deserializer.deserialize_any(SectionsVisitor(std::marker::PhantomData))
+    }
+}
+
+/// A component together with the name of its section in the configuration file
+pub struct ConfigEntry {
+    /// Unique name of the component, the section name followed by `#2`, `#3`, etc.
+    /// for further entries of an array of tables
+    pub name: &'static str,
+    pub section: &'static str,
+    pub common: CommonOptions,
+    pub component: BoxedComponent,
+}
+
+impl ConfigEntry {
+    /// Whether `name` given on the command line refers to this component,
+    /// either by its unique name or by its section name
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.section == name
+    }
+}
+
 /// Configuration for all components and the global settings
-/// The order of the components in the vector is the order they appear in the configuration file
-/// and is the order in which they should be printed
+/// The order of the components in the vector is the order in which they should be printed
+/// Components are sorted by their `order` option, which defaults to their index in the configuration file
 /// This way, users can configure the order of components by shifting lines in the config file
 pub struct Config {
-    pub components: Vec<BoxedComponent>,
+    pub components: Vec<ConfigEntry>,
     pub global: GlobalConfig,
 }
 
+impl Config {
+    /// Check the global configuration and the configuration of all components
+    /// Each problem is prefixed by the name of its section
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+        if let Err(global_problems) = self.global.validate() {
+            problems.extend(global_problems.into_iter().map(|p| format!("global: {p}")));
+        }
+        for entry in &self.components {
+            if let (Some(ColorOption::Named(_)), false) =
+                (entry.common.color, entry.section == "banner")
+            {
+                problems.push(format!("{}: color must be true or false", entry.name));
+            }
+            if let Err(component_problems) = entry.component.validate(&self.global) {
+                problems.extend(
+                    component_problems
+                        .into_iter()
+                        .map(|p| format!("{}: {p}", entry.name)),
+                );
+            }
+        }
+        validation_result(problems)
+    }
+}
+
 // Deserializer that pushes components in the order they appear in the configuration file
 // Reference: https://serde.rs/deserialize-struct.html
 impl<'de> Deserialize<'de> for Config {
@@ -77,83 +296,124 @@ impl<'de> Deserialize<'de> for Config {
                     global: GlobalConfig::default(),
                 };
 
-                while let Some(key) = map.next_key()? {
-                    match key {
+                while let Some(key) = map.next_key::<Fields>()? {
+                    let entries = match key {
                         Fields::Global => {
                             result.global = map.next_value()?;
+                            continue;
                         }
                         Fields::Banner => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<Banner>()?));
+                            map.next_value::<Sections<Banner>>()?
+                                .into_entries(|section| {
+                                    let color = section.common.color;
+                                    section.into_entry(|banner| banner.with_color(color))
+                                })
                         }
-                        Fields::CgStats => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<CgStats>()?));
+                        Fields::CgStats => map
+                            .next_value::<Sections<CgStats>>()?
+                            .into_entries(|section| section.into_entry(identity)),
+                        Fields::Command => {
+                            map.next_value::<Sections<Command>>()?
+                                .into_entries(|section| {
+                                    let timeout = section.common.timeout;
+                                    section
+                                        .into_entry(|command| command.with_command_timeout(timeout))
+                                })
                         }
                         Fields::Docker => {
-                            result.components.push(Box::new(Docker {
-                                containers: map.next_value()?,
-                            }));
-                        }
-                        Fields::Fail2Ban => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<Fail2Ban>()?));
-                        }
-                        Fields::Filesystems => {
-                            result
-                                .components
-                                .push(Box::new(Filesystems::new(map.next_value()?)));
-                        }
-                        Fields::LastLogin => {
-                            result.components.push(Box::new(LastLogin {
-                                users: map.next_value()?,
-                            }));
-                        }
-                        Fields::LastRun => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<LastRun>()?));
-                        }
-                        Fields::LoadAvg => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<LoadAvg>()?));
-                        }
-                        Fields::Memory => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<Memory>()?));
+                            map.next_value::<Sections<_>>()?.into_entries(|section| {
+                                section.into_entry(|containers| Docker { containers })
+                            })
                         }
+                        Fields::Fail2Ban => map
+                            .next_value::<Sections<Fail2Ban>>()?
+                            .into_entries(|section| section.into_entry(identity)),
+                        Fields::Filesystems => map
+                            .next_value::<Sections<_>>()?
+                            .into_entries(|section| section.into_entry(Filesystems::new)),
+                        Fields::HostInfo => map
+                            .next_value::<Sections<HostInfo>>()?
+                            .into_entries(|section| section.into_entry(identity)),
+                        Fields::LastLogin => map
+                            .next_value::<Sections<LastLogin>>()?
+                            .into_entries(|section| section.into_entry(identity)),
+                        Fields::LastRun => map
+                            .next_value::<Sections<LastRun>>()?
+                            .into_entries(|section| section.into_entry(identity)),
+                        Fields::LoadAvg => map
+                            .next_value::<Sections<LoadAvg>>()?
+                            .into_entries(|section| section.into_entry(identity)),
+                        Fields::Memory => map
+                            .next_value::<Sections<Memory>>()?
+                            .into_entries(|section| section.into_entry(identity)),
+                        Fields::Processes => map
+                            .next_value::<Sections<Processes>>()?
+                            .into_entries(|section| section.into_entry(identity)),
                         Fields::ServiceStatus => {
-                            result.components.push(Box::new(ServiceStatus {
-                                services: map.next_value()?,
-                            }));
+                            map.next_value::<Sections<_>>()?.into_entries(|section| {
+                                section.into_entry(|services| ServiceStatus { services })
+                            })
                         }
                         Fields::UserServiceStatus => {
-                            result.components.push(Box::new(UserServiceStatus {
-                                services: map.next_value()?,
-                            }));
+                            map.next_value::<Sections<_>>()?.into_entries(|section| {
+                                section.into_entry(|services| UserServiceStatus { services })
+                            })
                         }
                         Fields::SSLCerts => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<SSLCerts>()?));
-                        }
-                        Fields::Uptime => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<Uptime>()?));
+                            map.next_value::<Sections<SSLCerts>>()?
+                                .into_entries(|section| {
+                                    let timeout = section.common.timeout;
+                                    section
+                                        .into_entry(|certs| certs.with_connection_timeout(timeout))
+                                })
                         }
+                        Fields::Temperatures => map
+                            .next_value::<Sections<Temperatures>>()?
+                            .into_entries(|section| section.into_entry(identity)),
+                        Fields::TimeSync => map
+                            .next_value::<Sections<TimeSync>>()?
+                            .into_entries(|section| section.into_entry(identity)),
+                        Fields::Uptime => map
+                            .next_value::<Sections<Uptime>>()?
+                            .into_entries(|section| section.into_entry(identity)),
+                        Fields::Users => map
+                            .next_value::<Sections<Users>>()?
+                            .into_entries(|section| section.into_entry(identity)),
                         Fields::Weather => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<Weather>()?));
+                            // The weather component has always used `timeout` for its HTTP request
+                            let mut section_number = 0;
+                            map.next_value::<Sections<Weather>>()?
+                                .into_entries(|section| {
+                                    let timeout = section.common.timeout;
+                                    section_number += 1;
+                                    section.into_entry(|weather| {
+                                        weather
+                                            .with_request_timeout(timeout)
+                                            .with_section_number(section_number)
+                                    })
+                                })
                         }
+                    };
+                    for (index, (common, component)) in entries.into_iter().enumerate() {
+                        let name = match index {
+                            0 => key.name(),
+                            // Leaked once per entry, the configuration lives as long as the program
+                            _ => {
+                                Box::leak(format!("{}#{}", key.name(), index + 1).into_boxed_str())
+                            }
+                        };
+                        result.components.push(ConfigEntry {
+                            name,
+                            section: key.name(),
+                            common,
+                            component,
+                        });
                     }
                 }
+                // Stable sort, so components with equal keys keep their file order
+                let mut indexed: Vec<_> = result.components.into_iter().enumerate().collect();
+                indexed.sort_by_key(|(index, entry)| entry.common.order.unwrap_or(*index as i32));
+                result.components = indexed.into_iter().map(|(_, entry)| entry).collect();
                 Ok(result)
             }
         }
@@ -161,3 +421,59 @@ impl<'de> Deserialize<'de> for Config {
         deserializer.deserialize_map(ConfigVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Deserialize)]
+    struct Options {
+        value: u32,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct File {
+        x: Sections<Options>,
+    }
+
+    #[test]
+    fn table_is_one_section() {
+        let file: File = toml::from_str("[x]\nvalue = 1\norder = 3\n").unwrap();
+        let [section] = &file.x.0[..] else {
+            panic!("expected one section");
+        };
+        assert_eq!(section.options.value, 1);
+        assert_eq!(section.common.order, Some(3));
+    }
+
+    #[test]
+    fn array_of_tables_is_one_section_each() {
+        let file: File =
+            toml::from_str("[[x]]\nvalue = 1\n\n[[x]]\nvalue = 2\ntimeout = \"5s\"\n").unwrap();
+        let [first, second] = &file.x.0[..] else {
+            panic!("expected two sections");
+        };
+        assert_eq!((first.options.value, first.common.timeout), (1, None));
+        assert_eq!(
+            (second.options.value, second.common.timeout),
+            (2, Some(Duration::from_secs(5)))
+        );
+    }
+
+    #[test]
+    fn other_values_are_not_sections() {
+        assert!(toml::from_str::<File>("x = 1\n").is_err());
+        assert!(toml::from_str::<File>("[x]\n").is_err());
+    }
+
+    #[test]
+    fn further_sections_are_numbered() {
+        let config: Config = toml::from_str(
+            "[[uptime]]\nprefix = \"Up\"\n\n[[uptime]]\nprefix = \"Up again\"\n\n[memory]\nswap_pos = \"beside\"\n",
+        )
+        .unwrap();
+        let names: Vec<_> = config.components.iter().map(|entry| entry.name).collect();
+        assert_eq!(names, ["uptime", "uptime#2", "memory"]);
+        assert!(config.components[1].matches("uptime"));
+    }
+}
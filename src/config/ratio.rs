@@ -0,0 +1,45 @@
+use serde::de::{self, Deserializer, Visitor};
+use std::fmt;
+
+/// Deserialize a ratio given either as a number (e.g. 0.01) or as a percentage (e.g. "1%")
+pub fn deserialize<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct RatioVisitor;
+
+    impl<'de> Visitor<'de> for RatioVisitor {
+        type Value = f64;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a ratio such as 0.01 or a percentage such as \"1%\"")
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<f64, E> {
+            value
+                .trim()
+                .strip_suffix('%')
+                .and_then(|percent| percent.trim_end().parse::<f64>().ok())
+                .map(|percent| percent / 100.0)
+                .ok_or_else(|| {
+                    E::custom(format!(
+                        "invalid percentage {value:?}, expected e.g. \"1%\""
+                    ))
+                })
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<f64, E> {
+            Ok(value)
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<f64, E> {
+            Ok(value as f64)
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<f64, E> {
+            Ok(value as f64)
+        }
+    }
+
+    deserializer.deserialize_any(RatioVisitor)
+}
@@ -1,16 +1,71 @@
 use clap::Parser;
+use std::any::Any;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::{JoinError, JoinHandle};
 
+// Everything in this crate prints through these instead of the standard macros,
+// so that the runner can collect the output of each component, see `output::capture`
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::output::print(format_args!($($arg)*))
+    };
+}
+
+macro_rules! println {
+    () => {
+        $crate::output::print(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+mod cache;
+mod colors;
 mod command;
 mod components;
 mod config;
 mod constants;
-use component::{BoxedComponent, Constraints};
+mod output;
+mod progress_bar;
+mod rounding;
+mod state_lock;
+use cache::CachedOutput;
+use component::{BoxedComponent, Constraints, PrepareReturn, Severity};
 use config::get_config::get_config;
+use config::global_config::{GlobalConfig, OutputFormat};
+use config::{ColorOption, ConfigEntry};
+use rounding::{round_duration, Granularity};
 mod component;
 
 #[derive(Parser, Debug)]
 struct Args {
     config_path: Option<String>,
+
+    /// Output format (overrides `output_format` from the configuration file)
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Render the MOTD without writing any state (e.g. to preview a configuration)
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Only check the configuration file and exit
+    #[arg(long)]
+    check_config: bool,
+
+    /// Print only these components (comma separated section names, e.g. cg_stats,load_avg)
+    #[arg(long, value_delimiter = ',')]
+    only: Vec<String>,
+
+    /// Do not print these components (comma separated section names)
+    #[arg(long, value_delimiter = ',')]
+    skip: Vec<String>,
 }
 
 #[tokio::main]
@@ -18,14 +73,138 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
     match get_config(args.config_path) {
-        Ok(config) => {
-            // Run the prepare phase for each component
+        Ok(_) if args.check_config => println!("Configuration OK"),
+        Ok(mut config) => {
+            if let Some(format) = args.format {
+                config.global.output_format = format;
+            }
+            if args.dry_run {
+                config.global.dry_run = true;
+            }
+            if let Some(name) = args
+                .only
+                .iter()
+                .find(|name| !config.components.iter().any(|c| c.matches(name)))
+            {
+                eprintln!("Component {name:?} given by --only is not configured");
+                std::process::exit(1);
+            }
+            config.components.retain(|c| {
+                c.common.enabled.unwrap_or(true)
+                    && (args.only.is_empty() || args.only.iter().any(|name| c.matches(name)))
+                    && !args.skip.iter().any(|name| c.matches(name))
+            });
+            // Escape sequences would end up verbatim in the Markdown document
+            if let OutputFormat::Markdown = config.global.output_format {
+                config.global.color = false;
+            }
+
+            let global_config = Arc::new(config.global);
+
+            // Run the prepare phase for all components concurrently, since most of them do IO
             // Allow each component to specify its sizing constraints (like min width)
-            let (components, constraints): (Vec<BoxedComponent>, Vec<Option<Constraints>>) = config
+            // Components with a fresh cached output are not run at all
+            let tasks: Vec<_> = config
                 .components
                 .into_iter()
-                .map(|component| component.prepare(&config.global))
-                .unzip();
+                .map(
+                    |ConfigEntry {
+                         name,
+                         common,
+                         component,
+                         ..
+                     }| {
+                        let timeout = common.timeout.or(global_config.default_timeout);
+                        // The JSON output needs the data of the components, not their text
+                        // A dry run is meant to preview the configuration, so it bypasses the cache
+                        let cache_ttl = common.cache_ttl.filter(|_| {
+                            matches!(global_config.output_format, OutputFormat::Text)
+                                && !global_config.dry_run
+                        });
+                        // Markdown output must stay free of escape sequences
+                        let component_config = match common.color {
+                            Some(ColorOption::Enabled(color))
+                                if matches!(global_config.output_format, OutputFormat::Text) =>
+                            {
+                                Arc::new(global_config.with_color(color))
+                            }
+                            _ => global_config.clone(),
+                        };
+                        let task = match cache_ttl.and_then(|ttl| cache::read(name, ttl)) {
+                            Some((cached, age)) => PrepareTask::Cached(cached, age),
+                            None => {
+                                let task_config = component_config.clone();
+                                PrepareTask::Running(tokio::spawn(run_with_timeout(
+                                    timeout,
+                                    move || {
+                                        component::set_current_component(name);
+                                        component.prepare(&task_config)
+                                    },
+                                )))
+                            }
+                        };
+                        (
+                            name,
+                            component_config,
+                            timeout,
+                            cache_ttl,
+                            common.inline,
+                            task,
+                        )
+                    },
+                )
+                .collect();
+
+            // Print each component as soon as it and all components before it are prepared
+            // The minimum widths of later components are not known yet, so no common width is used
+            if global_config.incremental_output
+                && matches!(global_config.output_format, OutputFormat::Text)
+            {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                tokio::spawn(async move {
+                    for task in tasks {
+                        let entry = finish_prepare(task).await.map(|(entry, _)| entry);
+                        if sender.send(entry).is_err() {
+                            break;
+                        }
+                    }
+                });
+                let (any_printed, severity) = print_text(&global_config, receiver, None).await?;
+                print_error_summary(&global_config, any_printed);
+                exit_with_status(&global_config, severity);
+                return Ok(());
+            }
+
+            // Await the tasks in configuration order so that the output order is preserved
+            let mut components: Vec<ComponentEntry> = vec![];
+            let mut constraints: Vec<Option<Constraints>> = vec![];
+            for task in tasks {
+                let (entry, entry_constraints) = finish_prepare(task).await?;
+                components.push(entry);
+                constraints.push(entry_constraints);
+            }
+
+            let severity = components
+                .iter()
+                .filter_map(ComponentEntry::severity)
+                .max()
+                .unwrap_or_default();
+
+            if let OutputFormat::Json = global_config.output_format {
+                let output: serde_json::Map<String, serde_json::Value> = components
+                    .iter()
+                    .filter_map(|entry| match &entry.prepared {
+                        Prepared::Component(component, _) => {
+                            Some((entry.name.to_string(), component.to_json()))
+                        }
+                        _ => None,
+                    })
+                    .filter(|(_, value)| !value.is_null())
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&output)?);
+                exit_with_status(&global_config, severity);
+                return Ok(());
+            }
 
             // The width to use is the maximum of all the component's minimum widths
             // Right now, min width is the only constraint
@@ -35,12 +214,408 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .filter_map(|x| x.min_width)
                 .max();
 
-            // Print each component with the given width
-            for component in components {
-                component.print(&config.global, width).await;
+            if let OutputFormat::Markdown = global_config.output_format {
+                print_markdown(components, width).await;
+                print_error_summary(&global_config, false);
+                exit_with_status(&global_config, severity);
+                return Ok(());
+            }
+
+            // All components are prepared already, so they are simply passed on in order
+            let (sender, receiver) = mpsc::unbounded_channel();
+            for entry in components {
+                let _ = sender.send(Ok(entry));
+            }
+            drop(sender);
+            let (any_printed, _) = print_text(&global_config, receiver, width).await?;
+            print_error_summary(&global_config, any_printed);
+            exit_with_status(&global_config, severity);
+        }
+        Err(e) => {
+            println!("Config Error: {}", e);
+            if args.check_config {
+                std::process::exit(1);
             }
         }
-        Err(e) => println!("Config Error: {}", e),
     }
     Ok(())
 }
+
+/// Prepare phase of a component, either running or replaced by a cached output
+enum PrepareTask {
+    Running(JoinHandle<Option<Result<PrepareReturn, String>>>),
+    Cached(CachedOutput, Duration),
+}
+
+/// A component whose prepare phase was started, in the order of the tasks in `main`
+type PendingEntry = (
+    &'static str,
+    Arc<GlobalConfig>,
+    Option<Duration>,
+    Option<Duration>,
+    bool,
+    PrepareTask,
+);
+
+/// A component after the prepare phase
+struct ComponentEntry {
+    name: &'static str,
+    /// The global configuration as seen by the component, see `GlobalConfig::with_color`
+    config: Arc<GlobalConfig>,
+    timeout: Option<Duration>,
+    cache_ttl: Option<Duration>,
+    /// Print the component on one line with neighbouring inline components
+    inline: bool,
+    prepared: Prepared,
+}
+
+impl ComponentEntry {
+    /// Text of the component for a shared line, if it is printed inline
+    /// Components that cannot be rendered inline are printed as usual
+    fn inline_text(&self) -> Option<String> {
+        match &self.prepared {
+            Prepared::Component(component, _) if self.inline => {
+                component.render_inline(&self.config)
+            }
+            _ => None,
+        }
+    }
+
+    /// Cached components are not prepared, so their severity is unknown
+    fn severity(&self) -> Option<Severity> {
+        match &self.prepared {
+            Prepared::Component(component, _) => Some(component.severity()),
+            _ => None,
+        }
+    }
+}
+
+/// Result of the prepare phase of a component
+enum Prepared {
+    /// Prepared component and its minimum width
+    Component(BoxedComponent, Option<usize>),
+    /// Output of a previous run replayed from the cache and its age
+    Cached(String, Duration),
+    TimedOut,
+    /// The prepare phase panicked with the given message
+    Panicked(String),
+}
+
+/// Print the components in text format with the given width as they are received
+/// Returns whether anything was printed and the highest severity of the components
+async fn print_text(
+    global_config: &GlobalConfig,
+    mut receiver: mpsc::UnboundedReceiver<Result<ComponentEntry, JoinError>>,
+    width: Option<usize>,
+) -> Result<(bool, Severity), JoinError> {
+    // Print each component with the given width
+    // Components are separated by `component_separator`, which is not
+    // printed for components that print nothing
+    let runtime = tokio::runtime::Handle::current();
+    let mut any_printed = false;
+    let mut severity = Severity::default();
+    // Entry received while collecting inline components, printed next
+    let mut next: Option<ComponentEntry> = None;
+    loop {
+        // Show what was printed so far while waiting for slow components
+        std::io::stdout().flush().ok();
+        let entry = match next.take() {
+            Some(entry) => entry,
+            None => match receiver.recv().await {
+                Some(entry) => entry?,
+                None => break,
+            },
+        };
+        severity = severity.max(entry.severity().unwrap_or_default());
+        // Consecutive inline components share one line
+        if let Some(text) = entry.inline_text() {
+            let mut texts = vec![text];
+            while let Some(entry) = receiver.recv().await {
+                let entry = entry?;
+                severity = severity.max(entry.severity().unwrap_or_default());
+                match entry.inline_text() {
+                    Some(text) => texts.push(text),
+                    None => {
+                        next = Some(entry);
+                        break;
+                    }
+                }
+            }
+            if any_printed {
+                global_config.print_separator();
+            }
+            any_printed = true;
+            println!("{}", texts.join(" | "));
+            continue;
+        }
+        let ComponentEntry {
+            name,
+            config: component_config,
+            timeout,
+            cache_ttl,
+            prepared,
+            ..
+        } = entry;
+        let is_empty = match &prepared {
+            Prepared::Component(component, _) => component.is_empty(),
+            Prepared::Cached(output, _) => output.is_empty(),
+            Prepared::TimedOut | Prepared::Panicked(_) => false,
+        };
+        if !is_empty {
+            if any_printed {
+                global_config.print_separator();
+            }
+            any_printed = true;
+        }
+        let (component, min_width) = match prepared {
+            Prepared::Component(component, min_width) => {
+                if global_config.debug_layout {
+                    eprintln!(
+                        "{name}: min_width {}, width {}",
+                        min_width.map_or("none".into(), |w| w.to_string()),
+                        width.map_or("none".into(), |w| w.to_string()),
+                    );
+                }
+                (component, min_width)
+            }
+            Prepared::Cached(output, age) => {
+                print!("{output}");
+                print_stale_marker(&component_config, Some(age));
+                continue;
+            }
+            Prepared::TimedOut => {
+                println!("{} timed out", name);
+                continue;
+            }
+            Prepared::Panicked(message) => {
+                print_panic(name, &message);
+                continue;
+            }
+        };
+        let data_age = component.data_age();
+        let runtime = runtime.clone();
+        let print_config = component_config.clone();
+        // Only the output of components that finished is printed
+        let printed = run_with_timeout(timeout, move || {
+            component::set_current_component(name);
+            let ((), output) =
+                output::capture(|| runtime.block_on(component.print(&print_config, width)));
+            (output, component::failed_on_this_thread())
+        })
+        .await;
+        match printed {
+            Some(Ok((output, failed))) => {
+                print!("{output}");
+                // Failures are not cached so that the next run tries again
+                if cache_ttl.is_some() && !failed {
+                    let cached = CachedOutput { min_width, output };
+                    if let Err(e) = cache::write(name, &cached) {
+                        eprintln!("Cannot cache {name}: {e}");
+                    }
+                }
+                print_stale_marker(&component_config, data_age);
+            }
+            Some(Err(message)) => {
+                component::report_failure_of(name, format!("panicked: {message}"));
+                print_panic(name, &message);
+            }
+            None => {
+                println!("{} timed out", name);
+                component::report_failure_of(name, "timed out");
+            }
+        }
+    }
+    Ok((any_printed, severity))
+}
+
+/// Wait for the prepare phase of a component to finish
+async fn finish_prepare(
+    (name, config, timeout, cache_ttl, inline, task): PendingEntry,
+) -> Result<(ComponentEntry, Option<Constraints>), JoinError> {
+    let (prepared, constraints) = match task {
+        PrepareTask::Cached(cached, age) => (
+            Prepared::Cached(cached.output, age),
+            Some(Constraints {
+                min_width: cached.min_width,
+            }),
+        ),
+        PrepareTask::Running(task) => match task.await? {
+            Some(Ok((component, constraints))) => {
+                let min_width = constraints.as_ref().and_then(|c| c.min_width);
+                (Prepared::Component(component, min_width), constraints)
+            }
+            Some(Err(message)) => {
+                component::report_failure_of(name, format!("panicked: {message}"));
+                (Prepared::Panicked(message), None)
+            }
+            None => {
+                component::report_failure_of(name, "timed out");
+                (Prepared::TimedOut, None)
+            }
+        },
+    };
+    let entry = ComponentEntry {
+        name,
+        config,
+        timeout,
+        cache_ttl,
+        inline,
+        prepared,
+    };
+    Ok((entry, constraints))
+}
+
+/// Print how old the data of a component is if it is older than `stale_after`
+fn print_stale_marker(global_config: &GlobalConfig, data_age: Option<Duration>) {
+    if let (Some(age), Some(stale_after)) = (data_age, global_config.stale_after) {
+        if age > stale_after {
+            println!(
+                "{}({} old){}",
+                colors::dim(global_config.color),
+                humantime::format_duration(round_duration(age, Granularity::Minutes)),
+                colors::reset(global_config.color)
+            );
+        }
+    }
+}
+
+/// Print the components as a Markdown document with one section per component
+/// Components without Markdown support are included as their plain text output
+async fn print_markdown(components: Vec<ComponentEntry>, width: Option<usize>) {
+    let runtime = tokio::runtime::Handle::current();
+    for ComponentEntry {
+        name,
+        config: component_config,
+        timeout,
+        prepared,
+        ..
+    } in components
+    {
+        println!("## {name}\n");
+        let component = match prepared {
+            Prepared::Component(component, _) => component,
+            Prepared::Cached(output, _) => {
+                print_code_block(&output);
+                continue;
+            }
+            Prepared::TimedOut => {
+                println!("_timed out_\n");
+                continue;
+            }
+            Prepared::Panicked(message) => {
+                println!("_panicked: {message}_\n");
+                continue;
+            }
+        };
+        if let Some(markdown) = component.to_markdown() {
+            println!("{}\n", markdown.trim_end());
+            continue;
+        }
+        let runtime = runtime.clone();
+        let printed = run_with_timeout(timeout, move || {
+            component::set_current_component(name);
+            output::capture(|| runtime.block_on(component.print(&component_config, width))).1
+        })
+        .await;
+        match printed {
+            Some(Ok(output)) => print_code_block(&output),
+            Some(Err(message)) => {
+                println!("_panicked: {message}_\n");
+                component::report_failure_of(name, format!("panicked: {message}"));
+            }
+            None => {
+                println!("_timed out_\n");
+                component::report_failure_of(name, "timed out");
+            }
+        }
+    }
+}
+
+/// Print plain text output of a component as a Markdown code block
+fn print_code_block(output: &str) {
+    let output = output.trim_end();
+    if !output.is_empty() {
+        println!("```\n{output}\n```\n");
+    }
+}
+
+/// Print which components failed and why, if the configuration asks for it
+/// The summary is separated from the components like another component if `separate` is set
+fn print_error_summary(global_config: &GlobalConfig, separate: bool) {
+    let failures = component::failures();
+    if !global_config.show_error_summary || failures.is_empty() {
+        return;
+    }
+    if separate {
+        global_config.print_separator();
+    }
+    let list = failures
+        .iter()
+        .map(|(name, reason)| {
+            // Some errors (e.g. of external commands) span multiple lines
+            format!("{name} ({})", reason.lines().next().unwrap_or_default())
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    println!(
+        "{}⚠ {} component{} failed: {list}{}",
+        colors::fg(termion::color::Yellow, global_config.color),
+        failures.len(),
+        if failures.len() == 1 { "" } else { "s" },
+        colors::reset(global_config.color),
+    );
+}
+
+/// Exit with the status requested by `severity_exit_code` and `error_exit_code`
+/// The higher status wins, so a failure does not hide a critical state
+fn exit_with_status(global_config: &GlobalConfig, severity: Severity) {
+    let mut status = 0;
+    if global_config.severity_exit_code {
+        status = severity as i32;
+    }
+    if global_config.error_exit_code && component::any_failed() {
+        status = status.max(1);
+    }
+    if status != 0 {
+        std::process::exit(status);
+    }
+}
+
+/// Print the panic of a component in place of its output
+fn print_panic(name: &str, message: &str) {
+    println!("{name} error: panicked: {message}");
+}
+
+/// Run `task` on its own thread and wait at most `timeout` for its result
+/// Returns None if the task timed out. The thread is then left behind and
+/// its result is dropped; it does not keep the process alive once main returns
+/// A panic of the task is returned as an error with the panic message
+async fn run_with_timeout<T, F>(timeout: Option<Duration>, task: F) -> Option<Result<T, String>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(task)).map_err(panic_message);
+        // The receiver is gone if the task timed out
+        let _ = sender.send(result);
+    });
+    let result = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, receiver).await.ok()?,
+        None => receiver.await,
+    };
+    // Panics are caught, so the thread always sends its result
+    Some(result.unwrap_or_else(|_| Err("thread exited without a result".into())))
+}
+
+/// Message passed to `panic!`, which is a string unless the panic was raised by `panic_any`
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".into(),
+        },
+    }
+}
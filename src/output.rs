@@ -0,0 +1,34 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+
+thread_local! {
+    /// Output of the component printing on this thread, see `capture`
+    static BUFFER: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Run `task` and return everything it printed with `print!` and `println!` instead of
+/// writing it to the standard output
+/// Each component prints on its own thread, so the output of a component that timed out
+/// and keeps running ends up in its own buffer, which is never printed
+pub fn capture<T>(task: impl FnOnce() -> T) -> (T, String) {
+    BUFFER.with(|buffer| *buffer.borrow_mut() = Some(String::new()));
+    let result = task();
+    let output = BUFFER.with(|buffer| buffer.borrow_mut().take().unwrap_or_default());
+    (result, output)
+}
+
+/// Target of the `print!` and `println!` macros of this crate
+pub fn print(args: fmt::Arguments) {
+    let captured = BUFFER.with(|buffer| match buffer.borrow_mut().as_mut() {
+        Some(buffer) => {
+            let _ = fmt::Write::write_fmt(buffer, args);
+            true
+        }
+        None => false,
+    });
+    if !captured {
+        // Like the standard macros, but a closed pipe (e.g. `rust-motd | head`) is not a panic
+        let _ = io::stdout().write_fmt(args);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::colors;
+use crate::config::global_config::{BarValuePosition, GlobalConfig};
+
+/// Characters filling one to seven eighths of a cell, used by `smooth` bars
+const EIGHTHS: [char; 7] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// Render a progress bar `width` characters wide (including the prefix and suffix)
+/// filled to `full_ratio` and colored according to the global configuration
+pub fn format_bar(global_config: &GlobalConfig, width: usize, full_ratio: f64) -> String {
+    let full_color = colors::full_color(global_config, full_ratio);
+    format_bar_with_color(global_config, width, full_ratio, full_color)
+}
+
+/// Like `format_bar`, but the filled part of the bar is printed with `full_color`
+pub fn format_bar_with_color(
+    global_config: &GlobalConfig,
+    width: usize,
+    full_ratio: f64,
+    full_color: String,
+) -> String {
+    render_bar(
+        global_config,
+        global_config.bar_width(width),
+        full_ratio,
+        full_color,
+    )
+}
+
+/// Render a bar of exactly `width` characters, i.e. without applying `max_bar_width`
+fn render_bar(
+    global_config: &GlobalConfig,
+    width: usize,
+    full_ratio: f64,
+    full_color: String,
+) -> String {
+    // Only the prefix and suffix are rendered if the width is too small for any bar characters
+    let without_ends_width = width
+        .saturating_sub(global_config.progress_suffix.len())
+        .saturating_sub(global_config.progress_prefix.len());
+
+    let cells = (without_ends_width as f64) * full_ratio.clamp(0.0, 1.0);
+    // Sub-character resolution only blends in with a full block fill character
+    let (bar_full, partial) =
+        if global_config.smooth && global_config.progress_full_character == '█' {
+            let eighths = (cells * 8.0).round() as usize;
+            match eighths % 8 {
+                0 => (eighths / 8, None),
+                rest => (eighths / 8, Some(EIGHTHS[rest - 1])),
+            }
+        } else {
+            (cells.round() as usize, None)
+        };
+    let bar_empty = without_ends_width
+        .saturating_sub(bar_full)
+        .saturating_sub(partial.is_some() as usize);
+
+    [
+        global_config.progress_prefix.to_string(),
+        full_color,
+        global_config
+            .progress_full_character
+            .to_string()
+            .repeat(bar_full),
+        partial.map(String::from).unwrap_or_default(),
+        colors::fg(global_config.progress_empty_color, global_config.color),
+        global_config
+            .progress_empty_character
+            .to_string()
+            .repeat(bar_empty),
+        colors::reset(global_config.color),
+        global_config.progress_suffix.to_string(),
+    ]
+    .join("")
+}
+
+/// Render a progress bar together with its numeric `value` placed according to
+/// `bar_value_position`. The result always takes `value` + 1 + `width` columns
+/// (with `width` limited to `max_bar_width`), so that bars of consecutive lines
+/// stay aligned whatever the position.
+pub fn format_bar_with_value(
+    global_config: &GlobalConfig,
+    width: usize,
+    full_ratio: f64,
+    full_color: String,
+    value: &str,
+) -> String {
+    let width = global_config.bar_width(width);
+    let total_width = value.chars().count() + 1 + width;
+    match global_config.bar_value_position {
+        BarValuePosition::Before => format!(
+            "{value} {}",
+            render_bar(global_config, width, full_ratio, full_color)
+        ),
+        BarValuePosition::After => format!(
+            "{} {value}",
+            render_bar(global_config, width, full_ratio, full_color)
+        ),
+        BarValuePosition::Inside => {
+            format_bar_with_text(global_config, total_width, full_ratio, full_color, value)
+        }
+        BarValuePosition::None => render_bar(global_config, total_width, full_ratio, full_color),
+    }
+}
+
+/// Like `format_bar_with_color`, but `text` replaces the bar characters in the middle of the bar
+/// Text over the empty part is printed with the default color to stay readable
+fn format_bar_with_text(
+    global_config: &GlobalConfig,
+    width: usize,
+    full_ratio: f64,
+    full_color: String,
+    text: &str,
+) -> String {
+    let without_ends_width = width
+        .saturating_sub(global_config.progress_suffix.len())
+        .saturating_sub(global_config.progress_prefix.len());
+    // No sub-character resolution, the partial cell would be hidden under the text anyway
+    let bar_full = ((without_ends_width as f64) * full_ratio.clamp(0.0, 1.0)).round() as usize;
+    let text: Vec<char> = text.trim().chars().take(without_ends_width).collect();
+    let text_start = (without_ends_width - text.len()) / 2;
+    let text_cells = text_start..text_start + text.len();
+
+    let empty_color = colors::fg(global_config.progress_empty_color, global_config.color);
+    let mut bar = global_config.progress_prefix.to_string();
+    let mut current_color = "";
+    for i in 0..without_ends_width {
+        let (cell_color, c) = match i {
+            i if i < bar_full && text_cells.contains(&i) => {
+                (full_color.as_str(), text[i - text_start])
+            }
+            i if i < bar_full => (full_color.as_str(), global_config.progress_full_character),
+            i if text_cells.contains(&i) => ("", text[i - text_start]),
+            _ => (empty_color.as_str(), global_config.progress_empty_character),
+        };
+        if current_color != cell_color {
+            bar += &colors::reset(global_config.color);
+            bar += cell_color;
+            current_color = cell_color;
+        }
+        bar.push(c);
+    }
+    bar += &colors::reset(global_config.color);
+    bar += &global_config.progress_suffix;
+    bar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bars without escape sequences and with distinguishable characters
+    fn plain_config() -> GlobalConfig {
+        let mut config = GlobalConfig::default();
+        config.color = false;
+        config.progress_full_character = '#';
+        config.progress_empty_character = '-';
+        config
+    }
+
+    #[test]
+    fn bar_fills_width_20() {
+        let config = plain_config();
+        assert_eq!(format_bar(&config, 20, 0.0), "[------------------]");
+        assert_eq!(format_bar(&config, 20, 0.5), "[#########---------]");
+        assert_eq!(format_bar(&config, 20, 1.0), "[##################]");
+        // Ratios outside of 0..1 do not overflow the bar
+        assert_eq!(format_bar(&config, 20, 1.5), "[##################]");
+    }
+
+    #[test]
+    fn bar_narrower_than_prefix_and_suffix() {
+        let config = plain_config();
+        assert_eq!(format_bar(&config, 2, 0.5), "[]");
+        assert_eq!(format_bar(&config, 1, 0.5), "[]");
+        assert_eq!(format_bar(&config, 0, 0.5), "[]");
+        assert_eq!(
+            format_bar_with_text(&config, 2, 0.5, "".into(), "50%"),
+            "[]"
+        );
+    }
+
+    #[test]
+    fn bar_with_value_at_width_20() {
+        let mut config = plain_config();
+        let bar = |config: &GlobalConfig| format_bar_with_value(config, 20, 0.5, "".into(), "50%");
+        assert_eq!(bar(&config), "50% [#########---------]");
+        config.bar_value_position = BarValuePosition::After;
+        assert_eq!(bar(&config), "[#########---------] 50%");
+        config.bar_value_position = BarValuePosition::Inside;
+        assert_eq!(bar(&config), "[#########50%----------]");
+        config.bar_value_position = BarValuePosition::None;
+        assert_eq!(bar(&config), "[###########-----------]");
+    }
+}
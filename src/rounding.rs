@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// Unit to which displayed durations are rounded
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Seconds,
+    Minutes,
+    Hours,
+    Days,
+}
+
+impl Granularity {
+    fn secs(self) -> u64 {
+        match self {
+            Granularity::Seconds => 1,
+            Granularity::Minutes => 60,
+            Granularity::Hours => 60 * 60,
+            Granularity::Days => 24 * 60 * 60,
+        }
+    }
+}
+
+/// Round `duration` to the nearest multiple of `granularity`, so that
+/// `humantime::format_duration` does not show meaningless precision
+/// Durations shorter than the granularity are rounded to whole seconds instead of to zero
+pub fn round_duration(duration: Duration, granularity: Granularity) -> Duration {
+    let secs = duration.as_secs();
+    let unit = granularity.secs();
+    if secs < unit {
+        return Duration::from_secs(secs);
+    }
+    Duration::from_secs((secs + unit / 2) / unit * unit)
+}
@@ -0,0 +1,37 @@
+use std::fs;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::thread;
+use std::time::Duration;
+
+/// How many times taking the lock is tried before giving up
+const LOCK_ATTEMPTS: u32 = 10;
+/// Pause between the attempts to take the lock
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Take an exclusive lock on `<state_file>.lock`, so that concurrent logins do not compute
+/// their output from the same previous state and overwrite each other's new state
+/// Another instance of rust-motd holding the lock is waited for only briefly, after which
+/// `None` is returned and the state should be left alone
+/// The lock is released when the returned file is closed
+pub fn lock_state_file(state_file: &str) -> io::Result<Option<fs::File>> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(format!("{state_file}.lock"))?;
+    for attempt in 1..=LOCK_ATTEMPTS {
+        // SAFETY: plain system call on a file descriptor we own
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+            return Ok(Some(file));
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::WouldBlock {
+            return Err(err);
+        }
+        if attempt < LOCK_ATTEMPTS {
+            thread::sleep(LOCK_RETRY_INTERVAL);
+        }
+    }
+    Ok(None)
+}
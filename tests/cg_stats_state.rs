@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::thread;
+
+/// Run rust-motd with the configuration in `config`
+fn run_motd(config: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rust-motd"))
+        .arg(config)
+        .output()
+        .expect("running rust-motd")
+}
+
+/// Concurrent logins run cg_stats against the same state file, which must
+/// always be left complete by one of them
+#[test]
+fn concurrent_runs_keep_the_state_file_valid() {
+    let dir = std::env::temp_dir().join(format!("rust-motd-cg-stats-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let service = dir.join("cgroup/system.slice/nginx.service");
+    fs::create_dir_all(&service).unwrap();
+    fs::write(dir.join("cgroup/cgroup.controllers"), "cpu memory\n").unwrap();
+    let state_file = dir.join("cg_stats.toml");
+    let config = dir.join("config.toml");
+    fs::write(
+        &config,
+        format!(
+            "[cg_stats]\nstate_file = {:?}\nthreshold = 0.0\ncgroup_root = {:?}\nslices = [\"system.slice\"]\ncpu_count = 1\n",
+            state_file,
+            dir.join("cgroup"),
+        ),
+    )
+    .unwrap();
+
+    for round in 1..=20u64 {
+        fs::write(
+            service.join("cpu.stat"),
+            format!("usage_usec {}\n", round * 1000),
+        )
+        .unwrap();
+        let runs: Vec<_> = (0..2)
+            .map(|_| {
+                let config = config.clone();
+                thread::spawn(move || run_motd(&config))
+            })
+            .collect();
+        for run in runs {
+            let output = run.join().unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(output.status.success(), "round {round}: {output:?}");
+            assert!(!stdout.contains("error"), "round {round}: {stdout}");
+        }
+
+        let state = fs::read_to_string(&state_file)
+            .unwrap_or_else(|e| panic!("round {round}: state file lost: {e}"));
+        let state: toml::Value = toml::from_str(&state)
+            .unwrap_or_else(|e| panic!("round {round}: torn state file: {e}\n{state}"));
+        assert_eq!(
+            state["slices"]["system.slice"]["nginx"]["usage_usec"].as_integer(),
+            Some(round as i64 * 1000),
+            "round {round}"
+        );
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}